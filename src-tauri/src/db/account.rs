@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::XtreamAccount;
+use super::get_db;
+
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS xtream_account SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON xtream_account TYPE string;
+    DEFINE FIELD IF NOT EXISTS username ON xtream_account TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS status ON xtream_account TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS exp_date ON xtream_account TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS is_trial ON xtream_account TYPE bool;
+    DEFINE FIELD IF NOT EXISTS active_connections ON xtream_account TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS max_connections ON xtream_account TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS created_at ON xtream_account TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS allowed_output_formats ON xtream_account TYPE array<string>;
+    DEFINE FIELD IF NOT EXISTS server_url ON xtream_account TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS server_port ON xtream_account TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS server_https_port ON xtream_account TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS server_protocol ON xtream_account TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS server_timezone ON xtream_account TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS updated_at ON xtream_account TYPE int;
+";
+
+// Xtream account record for SurrealDB, one per playlist
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct XtreamAccountRecord {
+    playlist_id: String,
+    username: Option<String>,
+    status: Option<String>,
+    exp_date: Option<i64>,
+    is_trial: bool,
+    active_connections: Option<i64>,
+    max_connections: Option<i64>,
+    created_at: Option<i64>,
+    allowed_output_formats: Vec<String>,
+    server_url: Option<String>,
+    server_port: Option<String>,
+    server_https_port: Option<String>,
+    server_protocol: Option<String>,
+    server_timezone: Option<String>,
+    updated_at: i64,
+}
+
+pub async fn save_xtream_account(account: XtreamAccount) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let record = XtreamAccountRecord {
+        playlist_id: account.playlist_id.clone(),
+        username: account.username,
+        status: account.status,
+        exp_date: account.exp_date,
+        is_trial: account.is_trial,
+        active_connections: account.active_connections,
+        max_connections: account.max_connections,
+        created_at: account.created_at,
+        allowed_output_formats: account.allowed_output_formats,
+        server_url: account.server_url,
+        server_port: account.server_port,
+        server_https_port: account.server_https_port,
+        server_protocol: account.server_protocol,
+        server_timezone: account.server_timezone,
+        updated_at: account.updated_at,
+    };
+    
+    let _: Option<XtreamAccountRecord> = db
+        .upsert(("xtream_account", account.playlist_id))
+        .content(record)
+        .await
+        .map_err(|e| format!("Failed to save Xtream account: {}", e))?;
+    
+    Ok(())
+}
+
+pub async fn get_xtream_account(playlist_id: String) -> Result<Option<XtreamAccount>, String> {
+    let db = get_db().await?;
+    
+    let result: Option<XtreamAccountRecord> = db
+        .select(("xtream_account", playlist_id))
+        .await
+        .map_err(|e| format!("Failed to get Xtream account: {}", e))?;
+    
+    Ok(result.map(|r| XtreamAccount {
+        playlist_id: r.playlist_id,
+        username: r.username,
+        status: r.status,
+        exp_date: r.exp_date,
+        is_trial: r.is_trial,
+        active_connections: r.active_connections,
+        max_connections: r.max_connections,
+        created_at: r.created_at,
+        allowed_output_formats: r.allowed_output_formats,
+        server_url: r.server_url,
+        server_port: r.server_port,
+        server_https_port: r.server_https_port,
+        server_protocol: r.server_protocol,
+        server_timezone: r.server_timezone,
+        updated_at: r.updated_at,
+    }))
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use surrealdb::RecordId;
+
+use crate::types::{CacheProgress, ChangeCounts};
+use super::{get_db, Keyed};
+
+/// Records per staged batch, and per statement when the batches are applied
+const BATCH_SIZE: usize = 500;
+
+// A streamed update spans many queries, and a SurrealDB transaction cannot,
+// so writes wait in `cache_stage` until `commit_stage` applies them at once.
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS cache_stage SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON cache_stage TYPE string;
+    DEFINE FIELD IF NOT EXISTS batch ON cache_stage TYPE int;
+    DEFINE FIELD IF NOT EXISTS kind ON cache_stage TYPE string;
+    DEFINE FIELD IF NOT EXISTS target ON cache_stage TYPE record;
+    DEFINE FIELD IF NOT EXISTS record ON cache_stage FLEXIBLE TYPE option<object>;
+    DEFINE INDEX IF NOT EXISTS idx_cache_stage_batch ON cache_stage FIELDS playlist_id, batch;
+    DEFINE INDEX IF NOT EXISTS idx_cache_stage_target ON cache_stage FIELDS target;
+";
+
+// A record with its id, as `INSERT` and `UPDATE ... CONTENT` expect it
+#[derive(Debug, Serialize)]
+pub(super) struct Row<T> {
+    pub(super) id: RecordId,
+    #[serde(flatten)]
+    pub(super) record: T,
+}
+
+/// How staged rows change their target
+#[derive(Clone, Copy)]
+enum Write {
+    Replace,
+    Move,
+    Delete,
+}
+
+impl Write {
+    fn kind(self) -> &'static str {
+        match self {
+            Write::Replace => "replace",
+            Write::Move => "move",
+            Write::Delete => "delete",
+        }
+    }
+}
+
+// A pending write to `target`: its new content, its new position, or its
+// deletion. Rows staged by one statement share a batch number.
+#[derive(Debug, Serialize)]
+struct StagedRow<T> {
+    playlist_id: String,
+    batch: usize,
+    kind: &'static str,
+    target: RecordId,
+    record: Option<T>,
+}
+
+/// Changes needed to turn the stored records of a playlist into a new set
+pub(super) struct Diff<T> {
+    inserts: Vec<(String, T)>,
+    updates: Vec<(String, T)>,
+    deletes: Vec<(String, T)>,
+    unchanged: usize,
+}
+
+impl<T: PartialEq> Diff<T> {
+    pub(super) fn new() -> Self {
+        Self {
+            inserts: Vec::new(),
+            updates: Vec::new(),
+            deletes: Vec::new(),
+            unchanged: 0,
+        }
+    }
+    
+    /// Sort an incoming record by how it differs from its stored version
+    pub(super) fn push(&mut self, key: String, record: T, stored: Option<T>) {
+        match stored {
+            None => self.inserts.push((key, record)),
+            Some(old) if old != record => self.updates.push((key, record)),
+            Some(_) => self.unchanged += 1,
+        }
+    }
+    
+    /// Delete the stored records no incoming record matched
+    pub(super) fn delete_rest(&mut self, existing: HashMap<String, T>) {
+        self.deletes.extend(existing);
+    }
+}
+
+impl<T> Diff<T> {
+    /// Number of records `stage` writes
+    pub(super) fn writes(&self) -> usize {
+        self.inserts.len() + self.updates.len() + self.deletes.len()
+    }
+    
+    pub(super) fn counts(&self) -> ChangeCounts {
+        ChangeCounts {
+            added: self.inserts.len(),
+            updated: self.updates.len(),
+            removed: self.deletes.len(),
+            unchanged: self.unchanged,
+        }
+    }
+}
+
+/// Reports staged records across every `stage` of one cache update
+pub(super) struct Progress<'a> {
+    playlist_id: String,
+    written: usize,
+    total: usize,
+    on_progress: &'a (dyn Fn(CacheProgress) + Send + Sync),
+}
+
+impl<'a> Progress<'a> {
+    pub(super) fn new(
+        playlist_id: &str,
+        total: usize,
+        on_progress: &'a (dyn Fn(CacheProgress) + Send + Sync),
+    ) -> Self {
+        Self {
+            playlist_id: playlist_id.to_string(),
+            written: 0,
+            total,
+            on_progress,
+        }
+    }
+    
+    pub(super) fn emit(&self, status: &str) {
+        (self.on_progress)(CacheProgress {
+            playlist_id: self.playlist_id.clone(),
+            written: self.written,
+            total: self.total,
+            status: status.to_string(),
+        });
+    }
+    
+    /// Count records compared after the update started, e.g. a streamed batch
+    pub(super) fn grow(&mut self, records: usize) {
+        self.total += records;
+    }
+    
+    fn advance(&mut self, records: usize) {
+        self.written += records;
+        self.emit("writing");
+    }
+}
+
+/// Every record of `table` belonging to a playlist, by record key
+pub(super) async fn load_keyed<T: DeserializeOwned>(
+    table: &str,
+    playlist_id: &str,
+) -> Result<HashMap<String, T>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query(format!(
+            "SELECT *, meta::id(id) AS key OMIT id FROM {} WHERE playlist_id = $playlist_id",
+            table
+        ))
+        .bind(("playlist_id", playlist_id.to_string()))
+        .await
+        .map_err(|e| format!("Failed to load {} records: {}", table, e))?;
+    
+    let records: Vec<Keyed<T>> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse {} records: {}", table, e))?;
+    
+    Ok(records.into_iter().map(|k| (k.key, k.record)).collect())
+}
+
+/// Compare by key. A key listed twice in `incoming` is an error, as one of
+/// the two records would otherwise be dropped without notice.
+pub(super) fn diff<T: PartialEq>(mut existing: HashMap<String, T>, incoming: Vec<(String, T)>) -> Result<Diff<T>, String> {
+    let mut unique: HashMap<String, T> = HashMap::with_capacity(incoming.len());
+    for (key, record) in incoming {
+        if unique.insert(key.clone(), record).is_some() {
+            return Err(format!("Duplicate record key {}", key));
+        }
+    }
+    let mut diff = Diff::new();
+    
+    for (key, record) in unique {
+        let stored = existing.remove(&key);
+        diff.push(key, record, stored);
+    }
+    diff.delete_rest(existing);
+    
+    Ok(diff)
+}
+
+/// Drop the staged writes of a playlist, e.g. left over from a failed update
+pub(super) async fn clear_stage(playlist_id: &str) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    db.query("DELETE FROM cache_stage WHERE playlist_id = $playlist_id")
+        .bind(("playlist_id", playlist_id.to_string()))
+        .await
+        .map_err(|e| format!("Failed to clear staged records: {}", e))?
+        .check()
+        .map_err(|e| format!("Failed to clear staged records: {}", e))?;
+    
+    Ok(())
+}
+
+async fn stage_rows<T: Serialize + Clone + 'static>(
+    playlist_id: &str,
+    table: &str,
+    chunk: &[(String, T)],
+    write: Write,
+    progress: &mut Progress<'_>,
+) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    // Numbered by the records staged before it, which is unique per update
+    let batch = progress.written;
+    let rows: Vec<StagedRow<Row<T>>> = chunk
+        .iter()
+        .map(|(key, record)| {
+            let target = RecordId::from_table_key(table, key.clone());
+            StagedRow {
+                playlist_id: playlist_id.to_string(),
+                batch,
+                kind: write.kind(),
+                target: target.clone(),
+                record: match write {
+                    Write::Delete => None,
+                    _ => Some(Row { id: target, record: record.clone() }),
+                },
+            }
+        })
+        .collect();
+    
+    db.query("INSERT INTO cache_stage $rows")
+        .bind(("rows", rows))
+        .await
+        .map_err(|e| format!("Failed to stage records: {}", e))?
+        .check()
+        .map_err(|e| format!("Failed to stage records: {}", e))?;
+    
+    progress.advance(chunk.len());
+    Ok(())
+}
+
+/// Write a diff to the staging table in batches. Nothing is visible in
+/// `table` until `commit_stage` runs.
+pub(super) async fn stage<T: Serialize + Clone + 'static>(
+    playlist_id: &str,
+    table: &str,
+    diff: &Diff<T>,
+    progress: &mut Progress<'_>,
+) -> Result<(), String> {
+    for chunk in diff.deletes.chunks(BATCH_SIZE) {
+        stage_rows(playlist_id, table, chunk, Write::Delete, progress).await?;
+    }
+    for chunk in diff.updates.chunks(BATCH_SIZE).chain(diff.inserts.chunks(BATCH_SIZE)) {
+        stage_rows(playlist_id, table, chunk, Write::Replace, progress).await?;
+    }
+    
+    Ok(())
+}
+
+/// Stage new positions of records whose content is unchanged. Only the
+/// position is written, so their search index entries stay as they are.
+pub(super) async fn stage_moves<T: Serialize + Clone + 'static>(
+    playlist_id: &str,
+    table: &str,
+    rows: &[(String, T)],
+    progress: &mut Progress<'_>,
+) -> Result<(), String> {
+    for chunk in rows.chunks(BATCH_SIZE) {
+        stage_rows(playlist_id, table, chunk, Write::Move, progress).await?;
+    }
+    
+    Ok(())
+}
+
+/// Apply every staged write of a playlist in a single transaction, so all
+/// `tables` change together or not at all. Removed and replaced records are
+/// deleted in one statement, then each batch is written with one `INSERT`
+/// or `UPDATE`.
+pub(super) async fn commit_stage(playlist_id: &str, tables: &[&str]) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let writes: String = tables
+        .iter()
+        .map(|table| {
+            format!(
+                "FOR $batch IN $batches[WHERE table_name = '{0}' AND kind = 'replace'].batch {{
+                    INSERT INTO {0} (SELECT VALUE record FROM cache_stage WHERE playlist_id = $playlist_id AND batch = $batch);
+                }};
+                FOR $batch IN $batches[WHERE table_name = '{0}' AND kind = 'move'].batch {{
+                    UPDATE (SELECT VALUE target FROM cache_stage WHERE playlist_id = $playlist_id AND batch = $batch)
+                        SET position = (SELECT VALUE record.position FROM ONLY cache_stage WHERE target = $parent.id LIMIT 1);
+                }};\n",
+                table
+            )
+        })
+        .collect();
+    
+    db.query(format!(
+        "BEGIN TRANSACTION;
+        LET $batches = SELECT batch, kind, meta::tb(target) AS table_name FROM cache_stage
+            WHERE playlist_id = $playlist_id GROUP BY batch, kind, table_name;
+        DELETE (SELECT VALUE target FROM cache_stage WHERE playlist_id = $playlist_id AND kind != 'move');
+        {}
+        DELETE FROM cache_stage WHERE playlist_id = $playlist_id;
+        COMMIT TRANSACTION;",
+        writes
+    ))
+    .bind(("playlist_id", playlist_id.to_string()))
+    .await
+    .map_err(|e| format!("Failed to write staged records: {}", e))?
+    .check()
+    .map_err(|e| format!("Failed to write staged records: {}", e))?;
+    
+    Ok(())
+}
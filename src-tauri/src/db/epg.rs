@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use crate::types::{EpgChannel, EpgNowNext, EpgProgramme, EpgSettings};
+use super::batch::Row;
+use super::get_db;
+
+/// Rows per `INSERT` statement when storing a guide
+const INSERT_CHUNK_SIZE: usize = 1000;
+
+/// How far ahead to look for the next programme
+const NEXT_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS epg_channel SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON epg_channel TYPE string;
+    DEFINE FIELD IF NOT EXISTS channel_id ON epg_channel TYPE string;
+    DEFINE FIELD IF NOT EXISTS display_names ON epg_channel TYPE array<string>;
+    DEFINE FIELD IF NOT EXISTS icon ON epg_channel TYPE option<string>;
+    DEFINE INDEX IF NOT EXISTS idx_epg_channel_playlist ON epg_channel FIELDS playlist_id;
+
+    DEFINE TABLE IF NOT EXISTS programme SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON programme TYPE string;
+    DEFINE FIELD IF NOT EXISTS channel_id ON programme TYPE string;
+    DEFINE FIELD IF NOT EXISTS start ON programme TYPE int;
+    DEFINE FIELD IF NOT EXISTS stop ON programme TYPE int;
+    DEFINE FIELD IF NOT EXISTS title ON programme TYPE string;
+    DEFINE FIELD IF NOT EXISTS description ON programme TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS categories ON programme TYPE array<string>;
+    DEFINE FIELD IF NOT EXISTS icon ON programme TYPE option<string>;
+    DEFINE INDEX IF NOT EXISTS idx_programme_playlist ON programme FIELDS playlist_id;
+    DEFINE INDEX IF NOT EXISTS idx_programme_channel ON programme FIELDS playlist_id, channel_id, start;
+
+    DEFINE TABLE IF NOT EXISTS epg_settings SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON epg_settings TYPE string;
+    DEFINE FIELD IF NOT EXISTS shift_hours ON epg_settings TYPE float;
+    DEFINE FIELD IF NOT EXISTS timezone ON epg_settings TYPE option<string>;
+";
+
+// EPG channel record for SurrealDB
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct EpgChannelRecord {
+    playlist_id: String,
+    channel_id: String,
+    display_names: Vec<String>,
+    icon: Option<String>,
+}
+
+// Programme record for SurrealDB
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ProgrammeRecord {
+    playlist_id: String,
+    channel_id: String,
+    start: i64,
+    stop: i64,
+    title: String,
+    description: Option<String>,
+    categories: Vec<String>,
+    icon: Option<String>,
+}
+
+// EPG settings record for SurrealDB
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct EpgSettingsRecord {
+    playlist_id: String,
+    shift_hours: f64,
+    timezone: Option<String>,
+}
+
+impl From<ProgrammeRecord> for EpgProgramme {
+    fn from(r: ProgrammeRecord) -> Self {
+        EpgProgramme {
+            channel_id: r.channel_id,
+            start: r.start,
+            stop: r.stop,
+            title: r.title,
+            description: r.description,
+            categories: r.categories,
+            icon: r.icon,
+        }
+    }
+}
+
+pub async fn get_epg_channels(playlist_id: String) -> Result<Vec<EpgChannel>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT * FROM epg_channel WHERE playlist_id = $playlist_id")
+        .bind(("playlist_id", playlist_id))
+        .await
+        .map_err(|e| format!("Failed to query EPG channels: {}", e))?;
+    
+    let records: Vec<EpgChannelRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse EPG channels: {}", e))?;
+    
+    Ok(records
+        .into_iter()
+        .map(|r| EpgChannel {
+            id: r.channel_id,
+            display_names: r.display_names,
+            icon: r.icon,
+        })
+        .collect())
+}
+
+pub async fn cache_programmes(playlist_id: String, programmes: Vec<EpgProgramme>) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let records: Vec<ProgrammeRecord> = programmes
+        .into_iter()
+        .map(|p| ProgrammeRecord {
+            playlist_id: playlist_id.clone(),
+            channel_id: p.channel_id,
+            start: p.start,
+            stop: p.stop,
+            title: p.title,
+            description: p.description,
+            categories: p.categories,
+            icon: p.icon,
+        })
+        .collect();
+    
+    for chunk in records.chunks(INSERT_CHUNK_SIZE) {
+        db.query("INSERT INTO programme $rows")
+            .bind(("rows", chunk.to_vec()))
+            .await
+            .map_err(|e| format!("Failed to insert programmes: {}", e))?
+            .check()
+            .map_err(|e| format!("Failed to insert programmes: {}", e))?;
+    }
+    
+    Ok(())
+}
+
+/// Current and upcoming programme for each requested EPG channel ID
+pub async fn get_epg_now_next(
+    playlist_id: String,
+    channel_ids: Vec<String>,
+    now: i64,
+) -> Result<HashMap<String, EpgNowNext>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT * FROM programme WHERE playlist_id = $playlist_id AND channel_id IN $channel_ids AND stop > $now AND start < $until ORDER BY start")
+        .bind(("playlist_id", playlist_id))
+        .bind(("channel_ids", channel_ids))
+        .bind(("now", now))
+        .bind(("until", now + NEXT_WINDOW_SECS))
+        .await
+        .map_err(|e| format!("Failed to query EPG: {}", e))?;
+    
+    let records: Vec<ProgrammeRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse EPG: {}", e))?;
+    
+    let mut guide: HashMap<String, EpgNowNext> = HashMap::new();
+    for record in records {
+        let entry = guide.entry(record.channel_id.clone()).or_default();
+        if record.start <= now && entry.now.is_none() {
+            entry.now = Some(record.into());
+        } else if record.start > now && entry.next.is_none() {
+            entry.next = Some(record.into());
+        }
+    }
+    
+    Ok(guide)
+}
+
+/// Programmes overlapping `[start, end)` for each requested EPG channel ID
+pub async fn get_epg_grid(
+    playlist_id: String,
+    channel_ids: Vec<String>,
+    start: i64,
+    end: i64,
+) -> Result<HashMap<String, Vec<EpgProgramme>>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT * FROM programme WHERE playlist_id = $playlist_id AND channel_id IN $channel_ids AND stop > $start AND start < $end ORDER BY start")
+        .bind(("playlist_id", playlist_id))
+        .bind(("channel_ids", channel_ids))
+        .bind(("start", start))
+        .bind(("end", end))
+        .await
+        .map_err(|e| format!("Failed to query EPG: {}", e))?;
+    
+    let records: Vec<ProgrammeRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse EPG: {}", e))?;
+    
+    let mut grid: HashMap<String, Vec<EpgProgramme>> = HashMap::new();
+    for record in records {
+        grid.entry(record.channel_id.clone()).or_default().push(record.into());
+    }
+    
+    Ok(grid)
+}
+
+pub async fn get_epg_settings(playlist_id: String) -> Result<EpgSettings, String> {
+    let db = get_db().await?;
+    
+    let result: Option<EpgSettingsRecord> = db
+        .select(("epg_settings", playlist_id.clone()))
+        .await
+        .map_err(|e| format!("Failed to get EPG settings: {}", e))?;
+    
+    Ok(result
+        .map(|r| EpgSettings {
+            playlist_id: r.playlist_id,
+            shift_hours: r.shift_hours,
+            timezone: r.timezone,
+        })
+        .unwrap_or(EpgSettings {
+            playlist_id,
+            ..Default::default()
+        }))
+}
+
+pub async fn save_epg_settings(settings: EpgSettings) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let record = EpgSettingsRecord {
+        playlist_id: settings.playlist_id.clone(),
+        shift_hours: settings.shift_hours,
+        timezone: settings.timezone.filter(|tz| !tz.trim().is_empty()),
+    };
+    
+    let _: Option<EpgSettingsRecord> = db
+        .upsert(("epg_settings", settings.playlist_id))
+        .content(record)
+        .await
+        .map_err(|e| format!("Failed to save EPG settings: {}", e))?;
+    
+    Ok(())
+}
+
+pub async fn clear_epg(playlist_id: String) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    db.query("DELETE FROM programme WHERE playlist_id = $playlist_id")
+        .bind(("playlist_id", playlist_id.clone()))
+        .await
+        .map_err(|e| format!("Failed to clear programmes: {}", e))?;
+    
+    db.query("DELETE FROM epg_channel WHERE playlist_id = $playlist_id")
+        .bind(("playlist_id", playlist_id))
+        .await
+        .map_err(|e| format!("Failed to clear EPG channels: {}", e))?;
+    
+    Ok(())
+}
+
+/// Playlist id the programmes of one guide source are stored under while it
+/// is being loaded, so a failed refresh leaves the current guide in place
+/// and a source that fails partway can be dropped on its own.
+pub fn epg_staging_id(playlist_id: &str, source_index: usize) -> String {
+    format!("{}:staging:{}", playlist_id, source_index)
+}
+
+/// Swap the programmes staged under `staging_ids` in for the playlist's
+/// guide and store its channels, in one transaction.
+pub async fn replace_epg(
+    playlist_id: String,
+    staging_ids: Vec<String>,
+    channels: Vec<EpgChannel>,
+) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    // A channel listed by several sources is stored once, the last one wins
+    let channels: HashMap<String, EpgChannel> = channels
+        .into_iter()
+        .map(|c| (c.id.clone(), c))
+        .collect();
+    let rows: Vec<Row<EpgChannelRecord>> = channels
+        .into_values()
+        .map(|channel| Row {
+            id: RecordId::from_table_key("epg_channel", format!("{}_{}", playlist_id, channel.id)),
+            record: EpgChannelRecord {
+                playlist_id: playlist_id.clone(),
+                channel_id: channel.id,
+                display_names: channel.display_names,
+                icon: channel.icon,
+            },
+        })
+        .collect();
+    
+    db.query(
+        "BEGIN TRANSACTION;
+        DELETE FROM programme WHERE playlist_id = $playlist_id;
+        DELETE FROM epg_channel WHERE playlist_id = $playlist_id;
+        UPDATE programme SET playlist_id = $playlist_id WHERE playlist_id IN $staging_ids;
+        INSERT INTO epg_channel $channels;
+        COMMIT TRANSACTION;",
+    )
+    .bind(("staging_ids", staging_ids))
+    .bind(("playlist_id", playlist_id))
+    .bind(("channels", rows))
+    .await
+    .map_err(|e| format!("Failed to replace EPG: {}", e))?
+    .check()
+    .map_err(|e| format!("Failed to replace EPG: {}", e))?;
+    
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use crate::types::EpgMapping;
+use super::batch::Row;
+use super::{get_db, item_key, original_id};
+
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS epg_mapping SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON epg_mapping TYPE string;
+    DEFINE FIELD IF NOT EXISTS channel_id ON epg_mapping TYPE string;
+    DEFINE FIELD IF NOT EXISTS epg_channel_id ON epg_mapping TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS confidence ON epg_mapping TYPE float;
+    DEFINE FIELD IF NOT EXISTS method ON epg_mapping TYPE string;
+    DEFINE FIELD IF NOT EXISTS manual ON epg_mapping TYPE bool;
+    DEFINE FIELD IF NOT EXISTS updated_at ON epg_mapping TYPE int;
+    DEFINE INDEX IF NOT EXISTS idx_epg_mapping_playlist ON epg_mapping FIELDS playlist_id;
+";
+
+// EPG mapping record for SurrealDB
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct EpgMappingRecord {
+    playlist_id: String,
+    channel_id: String,
+    epg_channel_id: Option<String>,
+    confidence: f64,
+    method: String,
+    manual: bool,
+    updated_at: i64,
+}
+
+impl From<EpgMappingRecord> for EpgMapping {
+    fn from(r: EpgMappingRecord) -> Self {
+        EpgMapping {
+            playlist_id: r.playlist_id,
+            channel_id: r.channel_id,
+            epg_channel_id: r.epg_channel_id,
+            confidence: r.confidence,
+            method: r.method,
+            manual: r.manual,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+impl From<EpgMapping> for EpgMappingRecord {
+    fn from(m: EpgMapping) -> Self {
+        EpgMappingRecord {
+            playlist_id: m.playlist_id,
+            channel_id: m.channel_id,
+            epg_channel_id: m.epg_channel_id,
+            confidence: m.confidence,
+            method: m.method,
+            manual: m.manual,
+            updated_at: m.updated_at,
+        }
+    }
+}
+
+/// The fields of a channel needed to link it to a guide
+#[derive(Debug, Deserialize, Clone)]
+pub struct EpgChannelRef {
+    pub id: String,
+    pub name: String,
+    pub tvg_id: Option<String>,
+    pub tvg_shift: Option<f32>,
+}
+
+// Only live channels are linked to a guide
+fn strip_playlist_prefix(playlist_id: &str, refs: Vec<EpgChannelRef>) -> Vec<EpgChannelRef> {
+    refs.into_iter()
+        .map(|r| EpgChannelRef {
+            id: original_id(playlist_id, "live", r.id),
+            ..r
+        })
+        .collect()
+}
+
+pub async fn get_live_channel_refs(playlist_id: String) -> Result<Vec<EpgChannelRef>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT meta::id(id) AS id, name, tvg_id, tvg_shift FROM channel WHERE playlist_id = $playlist_id AND content_type = 'live'")
+        .bind(("playlist_id", playlist_id.clone()))
+        .await
+        .map_err(|e| format!("Failed to query channels: {}", e))?;
+    
+    let records: Vec<EpgChannelRef> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channels: {}", e))?;
+    
+    Ok(strip_playlist_prefix(&playlist_id, records))
+}
+
+/// Guide-related fields of specific live channels; unknown ids are left out.
+pub async fn get_channel_refs(playlist_id: String, channel_ids: Vec<String>) -> Result<Vec<EpgChannelRef>, String> {
+    let db = get_db().await?;
+    
+    let records: Vec<RecordId> = channel_ids
+        .iter()
+        .map(|id| RecordId::from_table_key("channel", item_key(&playlist_id, "live", id)))
+        .collect();
+    
+    let mut result = db
+        .query("SELECT meta::id(id) AS id, name, tvg_id, tvg_shift FROM $records")
+        .bind(("records", records))
+        .await
+        .map_err(|e| format!("Failed to query channels: {}", e))?;
+    
+    let records: Vec<EpgChannelRef> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channels: {}", e))?;
+    
+    Ok(strip_playlist_prefix(&playlist_id, records))
+}
+
+pub async fn get_epg_mappings(playlist_id: String) -> Result<Vec<EpgMapping>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT * FROM epg_mapping WHERE playlist_id = $playlist_id")
+        .bind(("playlist_id", playlist_id))
+        .await
+        .map_err(|e| format!("Failed to query EPG mappings: {}", e))?;
+    
+    let records: Vec<EpgMappingRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse EPG mappings: {}", e))?;
+    
+    Ok(records.into_iter().map(EpgMapping::from).collect())
+}
+
+pub async fn get_epg_mappings_for(playlist_id: String, channel_ids: Vec<String>) -> Result<Vec<EpgMapping>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT * FROM epg_mapping WHERE playlist_id = $playlist_id AND channel_id IN $channel_ids")
+        .bind(("playlist_id", playlist_id))
+        .bind(("channel_ids", channel_ids))
+        .await
+        .map_err(|e| format!("Failed to query EPG mappings: {}", e))?;
+    
+    let records: Vec<EpgMappingRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse EPG mappings: {}", e))?;
+    
+    Ok(records.into_iter().map(EpgMapping::from).collect())
+}
+
+pub async fn save_epg_mapping(mapping: EpgMapping) -> Result<(), String> {
+    let db = get_db().await?;
+    let key = format!("{}_{}", mapping.playlist_id, mapping.channel_id);
+    
+    let _: Option<EpgMappingRecord> = db
+        .upsert(("epg_mapping", key))
+        .content(EpgMappingRecord::from(mapping))
+        .await
+        .map_err(|e| format!("Failed to save EPG mapping: {}", e))?;
+    
+    Ok(())
+}
+
+/// Replace every automatic mapping of a playlist in one transaction; manual
+/// overrides are kept.
+pub async fn replace_auto_epg_mappings(playlist_id: String, mappings: Vec<EpgMapping>) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let rows: Vec<Row<EpgMappingRecord>> = mappings
+        .into_iter()
+        .filter(|m| !m.manual)
+        .map(|m| Row {
+            id: RecordId::from_table_key("epg_mapping", format!("{}_{}", m.playlist_id, m.channel_id)),
+            record: EpgMappingRecord::from(m),
+        })
+        .collect();
+    
+    db.query(
+        "BEGIN TRANSACTION;
+        DELETE FROM epg_mapping WHERE playlist_id = $playlist_id AND manual = false;
+        FOR $row IN $rows { UPSERT $row.id CONTENT $row; };
+        COMMIT TRANSACTION;",
+    )
+    .bind(("playlist_id", playlist_id))
+    .bind(("rows", rows))
+    .await
+    .map_err(|e| format!("Failed to save EPG mappings: {}", e))?
+    .check()
+    .map_err(|e| format!("Failed to save EPG mappings: {}", e))?;
+    
+    Ok(())
+}
+
+pub async fn delete_epg_mapping(playlist_id: String, channel_id: String) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let _: Option<EpgMappingRecord> = db
+        .delete(("epg_mapping", format!("{}_{}", playlist_id, channel_id)))
+        .await
+        .map_err(|e| format!("Failed to delete EPG mapping: {}", e))?;
+    
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+use tokio::sync::Mutex;
+
+use crate::types::Favorite;
+use super::{get_db, item_key, ChannelRecord, Keyed};
+
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS favorite SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON favorite TYPE string;
+    DEFINE FIELD IF NOT EXISTS channel_id ON favorite TYPE string;
+    DEFINE FIELD IF NOT EXISTS content_type ON favorite TYPE string;
+    DEFINE FIELD IF NOT EXISTS position ON favorite TYPE int;
+    DEFINE FIELD IF NOT EXISTS added_at ON favorite TYPE int;
+    DEFINE INDEX IF NOT EXISTS idx_favorite_type ON favorite FIELDS playlist_id, content_type, position;
+";
+
+/// Favorites are added and reordered one at a time. Concurrent statements
+/// each see their own snapshot, so two adds would otherwise both read the
+/// same last position.
+static POSITION_LOCK: Mutex<()> = Mutex::const_new(());
+
+// Favorite record for SurrealDB
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct FavoriteRecord {
+    playlist_id: String,
+    channel_id: String,
+    content_type: String,
+    position: i64,
+    added_at: i64,
+}
+
+// Favorites share the key of the channel they point at, so they outlive
+// refreshes that rewrite the channel table.
+fn favorite_key(playlist_id: &str, content_type: &str, channel_id: &str) -> String {
+    item_key(playlist_id, content_type, channel_id)
+}
+
+async fn load_favorites(playlist_id: &str, content_type: Option<String>) -> Result<Vec<FavoriteRecord>, String> {
+    let db = get_db().await?;
+    
+    let query = if content_type.is_some() {
+        "SELECT * FROM favorite WHERE playlist_id = $playlist_id AND content_type = $content_type ORDER BY position"
+    } else {
+        "SELECT * FROM favorite WHERE playlist_id = $playlist_id ORDER BY content_type, position"
+    };
+    let mut result = db
+        .query(query)
+        .bind(("playlist_id", playlist_id.to_string()))
+        .bind(("content_type", content_type))
+        .await
+        .map_err(|e| format!("Failed to query favorites: {}", e))?;
+    
+    result.take(0).map_err(|e| format!("Failed to parse favorites: {}", e))
+}
+
+/// Mark a cached channel, movie or series as a favorite. It goes to the end
+/// of its content type's list; adding an existing favorite changes nothing.
+pub async fn add_favorite(playlist_id: String, content_type: String, channel_id: String) -> Result<Favorite, String> {
+    let _guard = POSITION_LOCK.lock().await;
+    let db = get_db().await?;
+    let key = favorite_key(&playlist_id, &content_type, &channel_id);
+    
+    let existing: Option<FavoriteRecord> = db
+        .select(("favorite", key.as_str()))
+        .await
+        .map_err(|e| format!("Failed to query favorite: {}", e))?;
+    
+    let channel: Option<ChannelRecord> = db
+        .select(("channel", key.as_str()))
+        .await
+        .map_err(|e| format!("Failed to query channel: {}", e))?;
+    
+    if let Some(record) = existing {
+        return Ok(to_favorite(record, channel));
+    }
+    let channel = channel.ok_or_else(|| format!("Channel {} is not cached for playlist {}", channel_id, playlist_id))?;
+    
+    let mut result = db
+        .query(
+            "CREATE type::thing('favorite', $key) CONTENT {
+                playlist_id: $playlist_id,
+                channel_id: $channel_id,
+                content_type: $content_type,
+                position: (math::max((SELECT VALUE position FROM favorite WHERE playlist_id = $playlist_id AND content_type = $content_type)) ?? -1) + 1,
+                added_at: $added_at,
+            }",
+        )
+        .bind(("key", key))
+        .bind(("playlist_id", playlist_id))
+        .bind(("channel_id", channel_id))
+        .bind(("content_type", channel.content_type.clone()))
+        .bind(("added_at", chrono::Utc::now().timestamp()))
+        .await
+        .map_err(|e| format!("Failed to save favorite: {}", e))?;
+    let saved: Option<FavoriteRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to save favorite: {}", e))?;
+    
+    saved
+        .map(|record| to_favorite(record, Some(channel)))
+        .ok_or_else(|| "Failed to save favorite".to_string())
+}
+
+pub async fn remove_favorite(playlist_id: String, content_type: String, channel_id: String) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let _: Option<FavoriteRecord> = db
+        .delete(("favorite", favorite_key(&playlist_id, &content_type, &channel_id)))
+        .await
+        .map_err(|e| format!("Failed to delete favorite: {}", e))?;
+    
+    Ok(())
+}
+
+/// Favorites of a playlist in user order. A favorite whose channel is gone
+/// from the latest refresh is still listed, with `channel` unset.
+pub async fn get_favorites(playlist_id: String, content_type: Option<String>) -> Result<Vec<Favorite>, String> {
+    let db = get_db().await?;
+    let favorites = load_favorites(&playlist_id, content_type).await?;
+    
+    let records: Vec<RecordId> = favorites
+        .iter()
+        .map(|f| RecordId::from_table_key("channel", favorite_key(&f.playlist_id, &f.content_type, &f.channel_id)))
+        .collect();
+    
+    let mut result = db
+        .query("SELECT *, meta::id(id) AS key OMIT id FROM $records")
+        .bind(("records", records))
+        .await
+        .map_err(|e| format!("Failed to query channels: {}", e))?;
+    
+    let channels: Vec<Keyed<ChannelRecord>> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channels: {}", e))?;
+    let mut channels: HashMap<String, ChannelRecord> = channels
+        .into_iter()
+        .map(|c| (c.key, c.record))
+        .collect();
+    
+    Ok(favorites
+        .into_iter()
+        .map(|f| {
+            let channel = channels.remove(&favorite_key(&f.playlist_id, &f.content_type, &f.channel_id));
+            to_favorite(f, channel)
+        })
+        .collect())
+}
+
+/// Put a content type's favorites in the given order. Favorites missing from
+/// `channel_ids` keep their relative order after the listed ones.
+pub async fn reorder_favorites(
+    playlist_id: String,
+    content_type: String,
+    channel_ids: Vec<String>,
+) -> Result<Vec<Favorite>, String> {
+    let _guard = POSITION_LOCK.lock().await;
+    let db = get_db().await?;
+    let mut favorites = load_favorites(&playlist_id, Some(content_type.clone())).await?;
+    
+    let order: HashMap<&str, usize> = channel_ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (id.as_str(), index))
+        .collect();
+    favorites.sort_by_key(|f| order.get(f.channel_id.as_str()).copied().unwrap_or(usize::MAX));
+    
+    #[derive(Serialize)]
+    struct PositionUpdate {
+        id: RecordId,
+        position: i64,
+    }
+    
+    let updates: Vec<PositionUpdate> = favorites
+        .iter()
+        .enumerate()
+        .map(|(position, f)| PositionUpdate {
+            id: RecordId::from_table_key("favorite", favorite_key(&f.playlist_id, &f.content_type, &f.channel_id)),
+            position: position as i64,
+        })
+        .collect();
+    
+    db.query("BEGIN TRANSACTION; FOR $row IN $updates { UPDATE $row.id SET position = $row.position; }; COMMIT TRANSACTION;")
+        .bind(("updates", updates))
+        .await
+        .map_err(|e| format!("Failed to reorder favorites: {}", e))?
+        .check()
+        .map_err(|e| format!("Failed to reorder favorites: {}", e))?;
+    
+    get_favorites(playlist_id, Some(content_type)).await
+}
+
+fn to_favorite(record: FavoriteRecord, channel: Option<ChannelRecord>) -> Favorite {
+    Favorite {
+        channel: channel.map(|c| c.into_cached(record.channel_id.clone())),
+        playlist_id: record.playlist_id,
+        channel_id: record.channel_id,
+        content_type: record.content_type,
+        position: record.position,
+        added_at: record.added_at,
+    }
+}
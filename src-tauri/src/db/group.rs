@@ -0,0 +1,567 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use crate::types::{CachedCategory, CategoryOverride, ChannelGroup, ChannelSource, GroupChannel};
+use super::{get_db, item_key, ChannelRecord, Keyed};
+
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS channel_group SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS name ON channel_group TYPE string;
+    DEFINE FIELD IF NOT EXISTS position ON channel_group TYPE int;
+    DEFINE FIELD IF NOT EXISTS channels ON channel_group TYPE array<object>;
+    DEFINE FIELD IF NOT EXISTS channels[*].playlist_id ON channel_group TYPE string;
+    DEFINE FIELD IF NOT EXISTS channels[*].content_type ON channel_group TYPE string;
+    DEFINE FIELD IF NOT EXISTS channels[*].channel_id ON channel_group TYPE string;
+    DEFINE FIELD IF NOT EXISTS created_at ON channel_group TYPE int;
+
+    DEFINE TABLE IF NOT EXISTS category_override SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON category_override TYPE string;
+    DEFINE FIELD IF NOT EXISTS content_type ON category_override TYPE string;
+    DEFINE FIELD IF NOT EXISTS category_id ON category_override TYPE string;
+    DEFINE FIELD IF NOT EXISTS name ON category_override TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS hidden ON category_override TYPE bool DEFAULT false;
+    DEFINE FIELD IF NOT EXISTS position ON category_override TYPE option<int>;
+    DEFINE INDEX IF NOT EXISTS idx_category_override_type ON category_override FIELDS playlist_id, content_type;
+";
+
+// Channel group record for SurrealDB
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ChannelGroupRecord {
+    name: String,
+    position: i64,
+    channels: Vec<ChannelSource>,
+    created_at: i64,
+}
+
+// Category override record for SurrealDB
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct CategoryOverrideRecord {
+    playlist_id: String,
+    content_type: String,
+    category_id: String,
+    name: Option<String>,
+    hidden: bool,
+    position: Option<i64>,
+}
+
+impl From<CategoryOverrideRecord> for CategoryOverride {
+    fn from(r: CategoryOverrideRecord) -> Self {
+        CategoryOverride {
+            playlist_id: r.playlist_id,
+            content_type: r.content_type,
+            category_id: r.category_id,
+            name: r.name,
+            hidden: r.hidden,
+            position: r.position,
+        }
+    }
+}
+
+fn to_group(record: Keyed<ChannelGroupRecord>) -> ChannelGroup {
+    ChannelGroup {
+        id: record.key,
+        name: record.record.name,
+        position: record.record.position,
+        channels: record.record.channels,
+    }
+}
+
+/// Key of the channel record a group entry points at
+fn source_key(source: &ChannelSource) -> String {
+    item_key(&source.playlist_id, &source.content_type, &source.channel_id)
+}
+
+fn group_name(name: String) -> Result<String, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Group name cannot be empty".to_string());
+    }
+    Ok(name)
+}
+
+pub async fn get_channel_groups() -> Result<Vec<ChannelGroup>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT *, meta::id(id) AS key OMIT id FROM channel_group ORDER BY position")
+        .await
+        .map_err(|e| format!("Failed to query channel groups: {}", e))?;
+    
+    let records: Vec<Keyed<ChannelGroupRecord>> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channel groups: {}", e))?;
+    
+    Ok(records.into_iter().map(to_group).collect())
+}
+
+async fn get_channel_group(group_id: &str) -> Result<ChannelGroup, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT *, meta::id(id) AS key OMIT id FROM $group")
+        .bind(("group", RecordId::from_table_key("channel_group", group_id)))
+        .await
+        .map_err(|e| format!("Failed to query channel group: {}", e))?;
+    
+    let record: Option<Keyed<ChannelGroupRecord>> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channel group: {}", e))?;
+    
+    record
+        .map(to_group)
+        .ok_or_else(|| format!("Channel group {} not found", group_id))
+}
+
+/// New empty group, placed after the existing ones
+pub async fn create_channel_group(name: String) -> Result<ChannelGroup, String> {
+    let db = get_db().await?;
+    let name = group_name(name)?;
+    
+    let mut result = db
+        .query("RETURN math::max((SELECT VALUE position FROM channel_group)) ?? -1")
+        .await
+        .map_err(|e| format!("Failed to query channel groups: {}", e))?;
+    let last: Option<i64> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channel groups: {}", e))?;
+    
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let _: Option<ChannelGroupRecord> = db
+        .create(("channel_group", id.as_str()))
+        .content(ChannelGroupRecord {
+            name,
+            position: last.unwrap_or(-1) + 1,
+            channels: Vec::new(),
+            created_at: chrono::Utc::now().timestamp(),
+        })
+        .await
+        .map_err(|e| format!("Failed to create channel group: {}", e))?;
+    
+    get_channel_group(&id).await
+}
+
+pub async fn rename_channel_group(group_id: String, name: String) -> Result<ChannelGroup, String> {
+    let db = get_db().await?;
+    let name = group_name(name)?;
+    
+    db.query("UPDATE $group SET name = $name")
+        .bind(("group", RecordId::from_table_key("channel_group", group_id.as_str())))
+        .bind(("name", name))
+        .await
+        .map_err(|e| format!("Failed to rename channel group: {}", e))?;
+    
+    get_channel_group(&group_id).await
+}
+
+pub async fn delete_channel_group(group_id: String) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let _: Option<ChannelGroupRecord> = db
+        .delete(("channel_group", group_id))
+        .await
+        .map_err(|e| format!("Failed to delete channel group: {}", e))?;
+    
+    Ok(())
+}
+
+/// Put the groups in the given order; unlisted groups follow in their current order.
+pub async fn reorder_channel_groups(group_ids: Vec<String>) -> Result<Vec<ChannelGroup>, String> {
+    let db = get_db().await?;
+    let mut groups = get_channel_groups().await?;
+    
+    let order: HashMap<&str, usize> = group_ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (id.as_str(), index))
+        .collect();
+    groups.sort_by_key(|g| order.get(g.id.as_str()).copied().unwrap_or(usize::MAX));
+    
+    #[derive(Serialize)]
+    struct PositionUpdate {
+        id: RecordId,
+        position: i64,
+    }
+    
+    let updates: Vec<PositionUpdate> = groups
+        .iter()
+        .enumerate()
+        .map(|(position, g)| PositionUpdate {
+            id: RecordId::from_table_key("channel_group", g.id.as_str()),
+            position: position as i64,
+        })
+        .collect();
+    
+    db.query("BEGIN TRANSACTION; FOR $row IN $updates { UPDATE $row.id SET position = $row.position; }; COMMIT TRANSACTION;")
+        .bind(("updates", updates))
+        .await
+        .map_err(|e| format!("Failed to reorder channel groups: {}", e))?
+        .check()
+        .map_err(|e| format!("Failed to reorder channel groups: {}", e))?;
+    
+    get_channel_groups().await
+}
+
+/// Replace a group's channels; their order is the group's order.
+pub async fn set_channel_group_channels(
+    group_id: String,
+    channels: Vec<ChannelSource>,
+) -> Result<ChannelGroup, String> {
+    let db = get_db().await?;
+    
+    let mut seen = HashSet::with_capacity(channels.len());
+    let unique: Vec<ChannelSource> = channels
+        .into_iter()
+        .filter(|channel| seen.insert(source_key(channel)))
+        .collect();
+    
+    let mut result = db
+        .query("UPDATE $group SET channels = $channels")
+        .bind(("group", RecordId::from_table_key("channel_group", group_id.as_str())))
+        .bind(("channels", unique))
+        .await
+        .map_err(|e| format!("Failed to update channel group: {}", e))?;
+    
+    let updated: Vec<ChannelGroupRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to update channel group: {}", e))?;
+    if updated.is_empty() {
+        return Err(format!("Channel group {} not found", group_id));
+    }
+    
+    get_channel_group(&group_id).await
+}
+
+/// Append a cached channel of any playlist to a group
+pub async fn add_to_channel_group(group_id: String, source: ChannelSource) -> Result<ChannelGroup, String> {
+    let db = get_db().await?;
+    let group = get_channel_group(&group_id).await?;
+    
+    let channel: Option<ChannelRecord> = db
+        .select(("channel", source_key(&source)))
+        .await
+        .map_err(|e| format!("Failed to query channel: {}", e))?;
+    if channel.is_none() {
+        return Err(format!(
+            "Channel {} is not cached for playlist {}",
+            source.channel_id, source.playlist_id
+        ));
+    }
+    
+    let mut channels = group.channels;
+    channels.push(source);
+    set_channel_group_channels(group_id, channels).await
+}
+
+pub async fn remove_from_channel_group(group_id: String, source: ChannelSource) -> Result<ChannelGroup, String> {
+    let group = get_channel_group(&group_id).await?;
+    let key = source_key(&source);
+    
+    let channels = group
+        .channels
+        .into_iter()
+        .filter(|c| source_key(c) != key)
+        .collect();
+    set_channel_group_channels(group_id, channels).await
+}
+
+/// Channels of a group in the user's order. Channels missing from their
+/// playlist's cache are left out until a refresh brings them back.
+pub async fn get_channel_group_channels(group_id: String) -> Result<Vec<GroupChannel>, String> {
+    let db = get_db().await?;
+    let group = get_channel_group(&group_id).await?;
+    
+    let records: Vec<RecordId> = group
+        .channels
+        .iter()
+        .map(|c| RecordId::from_table_key("channel", source_key(c)))
+        .collect();
+    
+    let mut result = db
+        .query("SELECT *, meta::id(id) AS key OMIT id FROM $records")
+        .bind(("records", records))
+        .await
+        .map_err(|e| format!("Failed to query channels: {}", e))?;
+    
+    let channels: Vec<Keyed<ChannelRecord>> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channels: {}", e))?;
+    let mut channels: HashMap<String, ChannelRecord> = channels
+        .into_iter()
+        .map(|c| (c.key, c.record))
+        .collect();
+    
+    Ok(group
+        .channels
+        .into_iter()
+        .filter_map(|source| {
+            let record = channels.remove(&source_key(&source))?;
+            Some(GroupChannel {
+                channel: record.into_cached(source.channel_id),
+                playlist_id: source.playlist_id,
+            })
+        })
+        .collect())
+}
+
+// Overrides are keyed like the category plus its content type, so they
+// survive the category table being rewritten on refresh.
+fn override_id(playlist_id: &str, content_type: &str, category_id: &str) -> RecordId {
+    RecordId::from_table_key("category_override", format!("{}_{}_{}", playlist_id, content_type, category_id))
+}
+
+pub async fn get_category_overrides(
+    playlist_id: String,
+    content_type: String,
+) -> Result<Vec<CategoryOverride>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT * FROM category_override WHERE playlist_id = $playlist_id AND content_type = $content_type")
+        .bind(("playlist_id", playlist_id))
+        .bind(("content_type", content_type))
+        .await
+        .map_err(|e| format!("Failed to query category overrides: {}", e))?;
+    
+    let records: Vec<CategoryOverrideRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse category overrides: {}", e))?;
+    
+    Ok(records.into_iter().map(CategoryOverride::from).collect())
+}
+
+/// Leaves out channels of hidden categories; bind `$hidden` to the result
+/// of `hidden_categories`.
+pub(super) const NOT_HIDDEN: &str = "[content_type, category_id] NOT IN $hidden";
+
+/// Categories of a playlist the user hid, as `[content_type, category_id]` pairs
+pub(super) async fn hidden_categories(playlist_id: &str) -> Result<Vec<(String, String)>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT VALUE [content_type, category_id] FROM category_override WHERE playlist_id = $playlist_id AND hidden = true")
+        .bind(("playlist_id", playlist_id.to_string()))
+        .await
+        .map_err(|e| format!("Failed to query hidden categories: {}", e))?;
+    
+    result.take(0).map_err(|e| format!("Failed to parse hidden categories: {}", e))
+}
+
+async fn upsert_category_override<T: Serialize + 'static>(
+    playlist_id: String,
+    content_type: String,
+    category_id: String,
+    field: &str,
+    value: T,
+) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    db.query(format!(
+        "UPSERT $id SET playlist_id = $playlist_id, content_type = $content_type, category_id = $category_id, {} = $value",
+        field
+    ))
+    .bind(("id", override_id(&playlist_id, &content_type, &category_id)))
+    .bind(("playlist_id", playlist_id))
+    .bind(("content_type", content_type))
+    .bind(("category_id", category_id))
+    .bind(("value", value))
+    .await
+    .map_err(|e| format!("Failed to save category override: {}", e))?
+    .check()
+    .map_err(|e| format!("Failed to save category override: {}", e))?;
+    
+    Ok(())
+}
+
+/// Show a provider category under another name; `None` restores the provider's name.
+pub async fn rename_category(
+    playlist_id: String,
+    content_type: String,
+    category_id: String,
+    name: Option<String>,
+) -> Result<(), String> {
+    let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    upsert_category_override(playlist_id, content_type, category_id, "name", name).await
+}
+
+pub async fn set_category_hidden(
+    playlist_id: String,
+    content_type: String,
+    category_id: String,
+    hidden: bool,
+) -> Result<(), String> {
+    upsert_category_override(playlist_id, content_type, category_id, "hidden", hidden).await
+}
+
+/// Put provider categories in the given order; unlisted ones follow in their
+/// original order.
+pub async fn reorder_categories(
+    playlist_id: String,
+    content_type: String,
+    category_ids: Vec<String>,
+) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    #[derive(Serialize)]
+    struct PositionUpdate {
+        id: RecordId,
+        category_id: String,
+        position: i64,
+    }
+    
+    let updates: Vec<PositionUpdate> = category_ids
+        .into_iter()
+        .enumerate()
+        .map(|(position, category_id)| PositionUpdate {
+            id: override_id(&playlist_id, &content_type, &category_id),
+            category_id,
+            position: position as i64,
+        })
+        .collect();
+    
+    db.query(
+        "BEGIN TRANSACTION;
+        UPDATE category_override SET position = NONE WHERE playlist_id = $playlist_id AND content_type = $content_type;
+        FOR $row IN $updates {
+            UPSERT $row.id SET playlist_id = $playlist_id, content_type = $content_type, category_id = $row.category_id, position = $row.position;
+        };
+        COMMIT TRANSACTION;",
+    )
+    .bind(("playlist_id", playlist_id))
+    .bind(("content_type", content_type))
+    .bind(("updates", updates))
+    .await
+    .map_err(|e| format!("Failed to reorder categories: {}", e))?
+    .check()
+    .map_err(|e| format!("Failed to reorder categories: {}", e))?;
+    
+    Ok(())
+}
+
+/// Rename, hide and reorder cached categories as the user set them up.
+/// Categories without a position follow the ordered ones in their original
+/// order, whatever the user renamed them to.
+pub(super) async fn apply_category_overrides(
+    playlist_id: String,
+    content_type: String,
+    categories: Vec<CachedCategory>,
+    include_hidden: bool,
+) -> Result<Vec<CachedCategory>, String> {
+    let overrides: HashMap<String, CategoryOverride> = get_category_overrides(playlist_id, content_type)
+        .await?
+        .into_iter()
+        .map(|o| (o.category_id.clone(), o))
+        .collect();
+    if overrides.is_empty() {
+        return Ok(categories);
+    }
+    
+    let mut categories: Vec<(Option<i64>, CachedCategory)> = categories
+        .into_iter()
+        .filter_map(|mut category| match overrides.get(&category.id) {
+            Some(o) if o.hidden && !include_hidden => None,
+            Some(o) => {
+                if let Some(name) = &o.name {
+                    category.name = name.clone();
+                }
+                Some((o.position, category))
+            }
+            None => Some((None, category)),
+        })
+        .collect();
+    // Stable, so categories without a position keep the order they came in
+    categories.sort_by_key(|(position, _)| position.unwrap_or(i64::MAX));
+    
+    Ok(categories.into_iter().map(|(_, category)| category).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::{block_on, category, channel};
+    use crate::db::{cache_playlist_data, count_cached_channels, get_cached_categories, get_cached_channels};
+
+    fn names(categories: Vec<CachedCategory>) -> Vec<String> {
+        categories.into_iter().map(|c| c.name).collect()
+    }
+
+    fn source(playlist_id: &str, channel_id: &str) -> ChannelSource {
+        ChannelSource {
+            playlist_id: playlist_id.to_string(),
+            content_type: "live".to_string(),
+            channel_id: channel_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn categories_without_a_position_keep_their_order() {
+        block_on(async {
+            let categories = vec![category("1", "Alpha"), category("2", "Beta"), category("3", "Gamma")];
+            cache_playlist_data("group-order".into(), categories, Vec::new(), |_| {}).await.unwrap();
+            let get = || get_cached_categories("group-order".into(), "live".into(), false);
+
+            rename_category("group-order".into(), "live".into(), "3".into(), Some("Aardvark".into())).await.unwrap();
+            assert_eq!(names(get().await.unwrap()), ["Alpha", "Beta", "Aardvark"]);
+
+            reorder_categories("group-order".into(), "live".into(), vec!["2".into()]).await.unwrap();
+            assert_eq!(names(get().await.unwrap()), ["Beta", "Alpha", "Aardvark"]);
+
+            reorder_categories("group-order".into(), "live".into(), vec!["3".into(), "1".into()]).await.unwrap();
+            assert_eq!(names(get().await.unwrap()), ["Aardvark", "Alpha", "Beta"]);
+        });
+    }
+
+    #[test]
+    fn hidden_categories_are_left_out() {
+        block_on(async {
+            let categories = vec![category("news", "News"), category("sports", "Sports")];
+            let channels = vec![channel("a", "A", "news"), channel("b", "B", "sports"), channel("c", "C", "sports")];
+            cache_playlist_data("group-hidden".into(), categories, channels, |_| {}).await.unwrap();
+
+            set_category_hidden("group-hidden".into(), "live".into(), "sports".into(), true).await.unwrap();
+            let shown = get_cached_categories("group-hidden".into(), "live".into(), false).await.unwrap();
+            let all = get_cached_categories("group-hidden".into(), "live".into(), true).await.unwrap();
+            assert_eq!(names(shown), ["News"]);
+            assert_eq!(names(all), ["News", "Sports"]);
+
+            let channels = get_cached_channels("group-hidden".into(), None, "live".into()).await.unwrap();
+            assert_eq!(channels.into_iter().map(|c| c.id).collect::<Vec<_>>(), ["a"]);
+            assert_eq!(count_cached_channels("group-hidden".into(), None, "live".into()).await.unwrap(), 1);
+            // Opening the hidden category itself still lists its channels
+            assert_eq!(count_cached_channels("group-hidden".into(), Some("sports".into()), "live".into()).await.unwrap(), 2);
+
+            set_category_hidden("group-hidden".into(), "live".into(), "sports".into(), false).await.unwrap();
+            assert_eq!(count_cached_channels("group-hidden".into(), None, "live".into()).await.unwrap(), 3);
+        });
+    }
+
+    #[test]
+    fn group_channels_are_deduplicated_in_order() {
+        block_on(async {
+            let channels = vec![channel("a", "A", "news"), channel("b", "B", "news")];
+            cache_playlist_data("group-dedupe".into(), vec![category("news", "News")], channels, |_| {}).await.unwrap();
+            let group = create_channel_group("Dedupe".into()).await.unwrap();
+
+            let sources = vec![
+                source("group-dedupe", "b"),
+                source("group-dedupe", "a"),
+                source("group-dedupe", "b"),
+                source("group-other", "b"),
+                source("group-dedupe", "a"),
+            ];
+            let updated = set_channel_group_channels(group.id.clone(), sources).await.unwrap();
+            let listed: Vec<(&str, &str)> = updated
+                .channels
+                .iter()
+                .map(|c| (c.playlist_id.as_str(), c.channel_id.as_str()))
+                .collect();
+            assert_eq!(listed, [("group-dedupe", "b"), ("group-dedupe", "a"), ("group-other", "b")]);
+
+            let updated = add_to_channel_group(group.id.clone(), source("group-dedupe", "a")).await.unwrap();
+            assert_eq!(updated.channels.len(), 3);
+
+            delete_channel_group(group.id).await.unwrap();
+        });
+    }
+}
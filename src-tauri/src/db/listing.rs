@@ -0,0 +1,311 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{ChannelPage, ChannelSort};
+use super::{get_db, group, ChannelRecord, Keyed};
+
+pub(super) const SCHEMA: &str = "
+    DEFINE INDEX IF NOT EXISTS idx_channel_position ON channel FIELDS playlist_id, content_type, position;
+    DEFINE INDEX IF NOT EXISTS idx_channel_added ON channel FIELDS playlist_id, content_type, added_at;
+";
+
+/// Largest page a single call returns
+const MAX_PAGE_SIZE: u32 = 1000;
+
+// Position after the last channel of a page. Every channel has a comparable
+// sort value: the cache writer always sets `position` and `added_at`, and
+// the optional fields are filled in by the query.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    value: serde_json::Value,
+    key: String,
+}
+
+// A channel with the value it was sorted by
+#[derive(Debug, Deserialize)]
+struct SortedChannel {
+    #[serde(flatten)]
+    channel: Keyed<ChannelRecord>,
+    sort_value: serde_json::Value,
+}
+
+/// Sort expression, the field to order by and whether it runs descending.
+/// Stored fields are ordered on directly so their indexes apply; computed
+/// values are ordered on through their `sort_value` alias.
+fn sort_expression(sort: &ChannelSort) -> (&'static str, &'static str, bool) {
+    match sort {
+        ChannelSort::Name => ("string::lowercase(name)", "sort_value", false),
+        ChannelSort::Added => ("added_at", "added_at", true),
+        ChannelSort::Provider => ("position", "position", false),
+        ChannelSort::Rating => ("(rating ?? -1)", "sort_value", true),
+        ChannelSort::Number => ("(channel_number ?? 4294967295)", "sort_value", false),
+    }
+}
+
+fn encode_cursor(cursor: &Cursor) -> Result<String, String> {
+    let json = serde_json::to_vec(cursor).map_err(|e| format!("Failed to encode cursor: {}", e))?;
+    Ok(URL_SAFE_NO_PAD.encode(json))
+}
+
+fn decode_cursor(cursor: &str) -> Result<Cursor, String> {
+    let json = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|e| format!("Invalid cursor: {}", e))?;
+    serde_json::from_slice(&json).map_err(|e| format!("Invalid cursor: {}", e))
+}
+
+// Without a category, every channel outside the hidden categories
+fn channel_filter(category_id: &Option<String>) -> String {
+    if category_id.is_some() {
+        "playlist_id = $playlist_id AND category_id = $category_id AND content_type = $content_type".to_string()
+    } else {
+        format!("playlist_id = $playlist_id AND content_type = $content_type AND {}", group::NOT_HIDDEN)
+    }
+}
+
+async fn hidden_unless_category(playlist_id: &str, category_id: &Option<String>) -> Result<Vec<(String, String)>, String> {
+    match category_id {
+        Some(_) => Ok(Vec::new()),
+        None => group::hidden_categories(playlist_id).await,
+    }
+}
+
+/// One page of a playlist's channels, optionally limited to a category.
+/// Pass the returned `next_cursor` back to get the following page; it is
+/// only valid with the same filters and sort.
+pub async fn get_channel_page(
+    playlist_id: String,
+    category_id: Option<String>,
+    content_type: String,
+    sort: ChannelSort,
+    limit: u32,
+    cursor: Option<String>,
+) -> Result<ChannelPage, String> {
+    let db = get_db().await?;
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let (expression, order_by, descending) = sort_expression(&sort);
+    let after = cursor.as_deref().map(decode_cursor).transpose()?;
+    
+    let hidden = hidden_unless_category(&playlist_id, &category_id).await?;
+    let mut conditions = channel_filter(&category_id);
+    if after.is_some() {
+        let op = if descending { "<" } else { ">" };
+        conditions.push_str(&format!(
+            " AND ({expr} {op} $after_value OR ({expr} = $after_value AND meta::id(id) > $after_key))",
+            expr = expression,
+            op = op
+        ));
+    }
+    
+    let query = format!(
+        "SELECT *, meta::id(id) AS key, {} AS sort_value OMIT id FROM channel WHERE {} ORDER BY {} {}, key LIMIT $limit",
+        expression,
+        conditions,
+        order_by,
+        if descending { "DESC" } else { "ASC" }
+    );
+    let (after_value, after_key) = match after {
+        Some(cursor) => (cursor.value, cursor.key),
+        None => (serde_json::Value::Null, String::new()),
+    };
+    
+    // One extra row tells whether there is a next page
+    let mut result = db
+        .query(query)
+        .bind(("playlist_id", playlist_id.clone()))
+        .bind(("category_id", category_id))
+        .bind(("content_type", content_type))
+        .bind(("after_value", after_value))
+        .bind(("after_key", after_key))
+        .bind(("hidden", hidden))
+        .bind(("limit", limit + 1))
+        .await
+        .map_err(|e| format!("Failed to query channels: {}", e))?;
+    
+    let mut rows: Vec<SortedChannel> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channels: {}", e))?;
+    
+    let has_more = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+    
+    let next_cursor = match rows.last() {
+        Some(last) if has_more => Some(encode_cursor(&Cursor {
+            value: last.sort_value.clone(),
+            key: last.channel.key.clone(),
+        })?),
+        _ => None,
+    };
+    
+    Ok(ChannelPage {
+        channels: rows
+            .into_iter()
+            .map(|row| row.channel.into_cached(&playlist_id))
+            .collect(),
+        next_cursor,
+    })
+}
+
+/// Number of channels `get_channel_page` pages through for these filters
+pub async fn count_cached_channels(
+    playlist_id: String,
+    category_id: Option<String>,
+    content_type: String,
+) -> Result<usize, String> {
+    let db = get_db().await?;
+    let hidden = hidden_unless_category(&playlist_id, &category_id).await?;
+    
+    let mut result = db
+        .query(format!(
+            "SELECT count() AS count FROM channel WHERE {} GROUP ALL",
+            channel_filter(&category_id)
+        ))
+        .bind(("hidden", hidden))
+        .bind(("playlist_id", playlist_id))
+        .bind(("category_id", category_id))
+        .bind(("content_type", content_type))
+        .await
+        .map_err(|e| format!("Failed to count channels: {}", e))?;
+    
+    #[derive(Debug, Deserialize)]
+    struct CountResult {
+        count: usize,
+    }
+    
+    let count: Option<CountResult> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse channel count: {}", e))?;
+    Ok(count.map(|c| c.count).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::db::testing::{block_on, category, channel};
+    use crate::db::{cache_playlist_data, count_cached_channels};
+    use crate::types::CachedChannel;
+
+    // Shared by the tests, which run in parallel, so it is cached once
+    async fn cache_channels() {
+        static CACHED: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+        CACHED.get_or_init(cache_channels_once).await;
+    }
+
+    // Names, dates, ratings and numbers are tied across several channels
+    async fn cache_channels_once() {
+        let channels = [
+            ("c1", "Alpha", 300, Some(5.0), Some(2)),
+            ("c2", "alpha", 100, None, None),
+            ("c3", "Beta", 300, Some(5.0), Some(2)),
+            ("c4", "beta", 200, Some(3.0), None),
+            ("c5", "Beta", 100, None, Some(1)),
+            ("c6", "Gamma", 300, Some(3.0), Some(2)),
+            ("c7", "Delta", 200, Some(8.0), None),
+        ]
+        .into_iter()
+        .map(|(id, name, added_at, rating, number)| CachedChannel {
+            added_at: Some(added_at),
+            rating,
+            channel_number: number,
+            ..channel(id, name, "all")
+        })
+        .collect();
+        cache_playlist_data("listing-paging".into(), vec![category("all", "All")], channels, |_| {})
+            .await
+            .unwrap();
+    }
+
+    async fn page_through(sort: ChannelSort) -> Vec<CachedChannel> {
+        let mut channels = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = get_channel_page(
+                "listing-paging".into(),
+                None,
+                "live".into(),
+                sort.clone(),
+                2,
+                cursor,
+            )
+            .await
+            .unwrap();
+            assert!(page.channels.len() <= 2);
+            channels.extend(page.channels);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                return channels;
+            }
+        }
+    }
+
+    // Pages hold every channel once, in the order of `compare` with ties
+    // broken by id
+    fn assert_paged(sort: ChannelSort, compare: fn(&CachedChannel, &CachedChannel) -> Ordering) {
+        block_on(async {
+            cache_channels().await;
+            let paged = page_through(sort).await;
+            let total = count_cached_channels("listing-paging".into(), None, "live".into())
+                .await
+                .unwrap();
+
+            let ids: Vec<&str> = paged.iter().map(|c| c.id.as_str()).collect();
+            assert_eq!(ids.len(), total);
+            assert_eq!(ids.iter().collect::<HashSet<_>>().len(), total);
+
+            let mut expected = paged.clone();
+            expected.sort_by(|a, b| compare(a, b).then_with(|| a.id.cmp(&b.id)));
+            let expected: Vec<&str> = expected.iter().map(|c| c.id.as_str()).collect();
+            assert_eq!(ids, expected);
+        });
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor {
+            value: serde_json::json!("beta"),
+            key: "listing_live_c3".to_string(),
+        };
+        let decoded = decode_cursor(&encode_cursor(&cursor).unwrap()).unwrap();
+        assert_eq!(decoded.value, cursor.value);
+        assert_eq!(decoded.key, cursor.key);
+    }
+
+    #[test]
+    fn invalid_cursor_is_rejected() {
+        assert!(decode_cursor("not a cursor!").is_err());
+        assert!(decode_cursor(&URL_SAFE_NO_PAD.encode("{\"value\":1}")).is_err());
+    }
+
+    #[test]
+    fn pages_by_name_ascending() {
+        assert_paged(ChannelSort::Name, |a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    }
+
+    #[test]
+    fn pages_by_provider_order() {
+        assert_paged(ChannelSort::Provider, |a, b| a.id.cmp(&b.id));
+    }
+
+    #[test]
+    fn pages_by_number_ascending() {
+        assert_paged(ChannelSort::Number, |a, b| {
+            a.channel_number.unwrap_or(u32::MAX).cmp(&b.channel_number.unwrap_or(u32::MAX))
+        });
+    }
+
+    #[test]
+    fn pages_by_date_added_descending() {
+        assert_paged(ChannelSort::Added, |a, b| b.added_at.cmp(&a.added_at));
+    }
+
+    #[test]
+    fn pages_by_rating_descending() {
+        assert_paged(ChannelSort::Rating, |a, b| {
+            b.rating.unwrap_or(-1.0).total_cmp(&a.rating.unwrap_or(-1.0))
+        });
+    }
+}
@@ -1,15 +1,54 @@
-use std::collections::HashMap;
+mod account;
+mod batch;
+mod epg;
+mod epg_mapping;
+mod favorite;
+mod group;
+mod listing;
+mod schedule;
+mod search;
+mod series;
+#[cfg(test)]
+mod testing;
+mod vod;
+
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
-use surrealdb::engine::local::{Db, RocksDb};
+use surrealdb::engine::local::Db;
 use surrealdb::Surreal;
 use tokio::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 
-use crate::types::{CachedCategory, CachedChannel, LastViewedState};
+use crate::types::{CacheProgress, CacheSummary, CachedCategory, ChangeCounts, CachedChannel, LastViewedState};
+
+pub use account::{get_xtream_account, save_xtream_account};
+pub use epg::{
+    cache_programmes, clear_epg, epg_staging_id, get_epg_channels, get_epg_grid, get_epg_now_next,
+    get_epg_settings, replace_epg, save_epg_settings,
+};
+pub use epg_mapping::{
+    EpgChannelRef, delete_epg_mapping, get_channel_refs, get_epg_mappings, get_epg_mappings_for, get_live_channel_refs,
+    replace_auto_epg_mappings, save_epg_mapping,
+};
+pub use favorite::{add_favorite, get_favorites, remove_favorite, reorder_favorites};
+pub use group::{
+    add_to_channel_group, create_channel_group, delete_channel_group, get_category_overrides, get_channel_group_channels,
+    get_channel_groups, remove_from_channel_group, rename_category, rename_channel_group, reorder_categories,
+    reorder_channel_groups, set_category_hidden, set_channel_group_channels,
+};
+pub use listing::{count_cached_channels, get_channel_page};
+pub use schedule::{
+    delete_scheduled_playlist, get_scheduled_playlist, get_scheduled_playlists, get_source_validator,
+    save_scheduled_playlist, save_source_validator, SourceValidator,
+};
+pub use search::{search_all_playlists, search_cached_channels};
+pub use series::{cache_series_details, get_cached_episodes, get_cached_seasons, get_cached_series};
+pub use vod::{get_vod_metadata, save_vod_metadata};
 
 static DB: OnceCell<Arc<Surreal<Db>>> = OnceCell::const_new();
 
+#[cfg_attr(test, allow(dead_code))]
 fn get_db_path() -> Result<PathBuf, String> {
     let app_dir = dirs::data_dir()
         .ok_or("Could not find data directory")?
@@ -25,8 +64,13 @@ pub async fn init_db() -> Result<Arc<Surreal<Db>>, String> {
         return Ok(db.clone());
     }
     
-    let db_path = get_db_path()?;
-    let db = Surreal::new::<RocksDb>(db_path)
+    #[cfg(not(test))]
+    let db = Surreal::new::<surrealdb::engine::local::RocksDb>(get_db_path()?)
+        .await
+        .map_err(|e| format!("Failed to connect to database: {}", e))?;
+    // Tests share one fresh in-memory database
+    #[cfg(test)]
+    let db = Surreal::new::<surrealdb::engine::local::Mem>(())
         .await
         .map_err(|e| format!("Failed to connect to database: {}", e))?;
     
@@ -55,6 +99,13 @@ pub async fn init_db() -> Result<Arc<Surreal<Db>>, String> {
         DEFINE FIELD IF NOT EXISTS content_type ON channel TYPE string;
         DEFINE FIELD IF NOT EXISTS stream_id ON channel TYPE option<string>;
         DEFINE FIELD IF NOT EXISTS container_extension ON channel TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS tvg_id ON channel TYPE option<string>;
+        DEFINE FIELD IF NOT EXISTS tvg_shift ON channel TYPE option<float>;
+        DEFINE FIELD IF NOT EXISTS channel_number ON channel TYPE option<int>;
+        DEFINE FIELD IF NOT EXISTS rating ON channel TYPE option<float>;
+        DEFINE FIELD IF NOT EXISTS added_at ON channel TYPE option<int>;
+        DEFINE FIELD IF NOT EXISTS position ON channel TYPE option<int>;
+        DEFINE FIELD IF NOT EXISTS attributes ON channel FLEXIBLE TYPE option<object>;
         DEFINE INDEX IF NOT EXISTS idx_channel_playlist ON channel FIELDS playlist_id;
         DEFINE INDEX IF NOT EXISTS idx_channel_category ON channel FIELDS playlist_id, category_id;
         DEFINE INDEX IF NOT EXISTS idx_channel_type ON channel FIELDS playlist_id, content_type;
@@ -69,6 +120,12 @@ pub async fn init_db() -> Result<Arc<Surreal<Db>>, String> {
     .await
     .map_err(|e| format!("Failed to define schema: {}", e))?;
     
+    for schema in [account::SCHEMA, batch::SCHEMA, epg::SCHEMA, epg_mapping::SCHEMA, favorite::SCHEMA, group::SCHEMA, listing::SCHEMA, schedule::SCHEMA, series::SCHEMA, vod::SCHEMA, search::SCHEMA] {
+        db.query(schema)
+            .await
+            .map_err(|e| format!("Failed to define schema: {}", e))?;
+    }
+    
     println!("SurrealDB initialized successfully");
     
     let db = Arc::new(db);
@@ -84,7 +141,7 @@ pub async fn get_db() -> Result<Arc<Surreal<Db>>, String> {
 }
 
 // Category record for SurrealDB
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 struct CategoryRecord {
     playlist_id: String,
     name: String,
@@ -103,6 +160,43 @@ struct ChannelRecord {
     content_type: String,
     stream_id: Option<String>,
     container_extension: Option<String>,
+    tvg_id: Option<String>,
+    tvg_shift: Option<f32>,
+    channel_number: Option<u32>,
+    rating: Option<f64>,
+    added_at: Option<i64>,
+    /// Index in the provider's list, for "provider order" sorting
+    position: Option<i64>,
+    #[serde(default)]
+    attributes: HashMap<String, String>,
+}
+
+// Equal when only the provider order differs. Moves are written on their
+// own, so one channel added at the top doesn't rewrite every channel below.
+impl PartialEq for ChannelRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.playlist_id == other.playlist_id
+            && self.category_id == other.category_id
+            && self.name == other.name
+            && self.url == other.url
+            && self.logo == other.logo
+            && self.group_title == other.group_title
+            && self.content_type == other.content_type
+            && self.stream_id == other.stream_id
+            && self.container_extension == other.container_extension
+            && self.tvg_id == other.tvg_id
+            && self.tvg_shift == other.tvg_shift
+            && self.channel_number == other.channel_number
+            && self.rating == other.rating
+            && self.added_at == other.added_at
+            && self.attributes == other.attributes
+    }
+}
+
+// Provider order of a channel whose content is unchanged
+#[derive(Debug, Serialize, Clone)]
+struct PositionRecord {
+    position: Option<i64>,
 }
 
 // LastViewed record for SurrealDB
@@ -114,90 +208,272 @@ struct LastViewedRecord {
     content_type: String,
 }
 
-pub async fn cache_playlist_data(
+// A record read back together with its key, via `meta::id(id) AS key`
+#[derive(Debug, Deserialize)]
+struct Keyed<T> {
+    key: String,
+    #[serde(flatten)]
+    record: T,
+}
+
+/// Record key of a cached category or channel. Providers number live
+/// streams, movies and series separately, so the content type is part of it.
+fn item_key(playlist_id: &str, content_type: &str, id: &str) -> String {
+    format!("{}_{}_{}", playlist_id, content_type, id)
+}
+
+/// The id a provider gave an item, from its `item_key`
+fn original_id(playlist_id: &str, content_type: &str, key: String) -> String {
+    match key.strip_prefix(&format!("{}_{}_", playlist_id, content_type)) {
+        Some(id) => id.to_string(),
+        None => key,
+    }
+}
+
+impl Keyed<ChannelRecord> {
+    fn into_cached(self, playlist_id: &str) -> CachedChannel {
+        let id = original_id(playlist_id, &self.record.content_type, self.key);
+        self.record.into_cached(id)
+    }
+}
+
+impl ChannelRecord {
+    fn into_cached(self, id: String) -> CachedChannel {
+        CachedChannel {
+            id,
+            name: self.name,
+            url: self.url,
+            logo: self.logo,
+            group_title: self.group_title,
+            content_type: self.content_type,
+            category_id: self.category_id,
+            stream_id: self.stream_id,
+            container_extension: self.container_extension,
+            tvg_id: self.tvg_id,
+            tvg_shift: self.tvg_shift,
+            channel_number: self.channel_number,
+            rating: self.rating,
+            added_at: self.added_at,
+            attributes: self.attributes,
+        }
+    }
+}
+
+/// Channels compared and staged per batch when caching a playlist
+pub const CACHE_CHUNK_SIZE: usize = 5_000;
+
+/// Writes the cache of one playlist batch by batch, so a playlist that is
+/// still being parsed never has to be held in memory as a whole. Nothing is
+/// visible until `finish` applies every staged write at once.
+pub struct CacheWriter<'a> {
     playlist_id: String,
-    categories: Vec<CachedCategory>,
-    channels: Vec<CachedChannel>,
-) -> Result<(), String> {
-    let db = get_db().await?;
-    
-    // Clear existing data for this playlist
-    db.query("DELETE FROM category WHERE playlist_id = $playlist_id")
-        .bind(("playlist_id", playlist_id.clone()))
-        .await
-        .map_err(|e| format!("Failed to clear categories: {}", e))?;
+    /// Stored channels no batch has listed yet
+    existing: HashMap<String, ChannelRecord>,
+    seen: HashSet<String>,
+    channels: ChangeCounts,
+    now: i64,
+    progress: batch::Progress<'a>,
+}
+
+impl<'a> CacheWriter<'a> {
+    pub async fn start(
+        playlist_id: String,
+        on_progress: &'a (dyn Fn(CacheProgress) + Send + Sync),
+    ) -> Result<Self, String> {
+        let existing = batch::load_keyed::<ChannelRecord>("channel", &playlist_id).await?;
+        batch::clear_stage(&playlist_id).await?;
+        
+        Ok(Self {
+            progress: batch::Progress::new(&playlist_id, 0, on_progress),
+            playlist_id,
+            existing,
+            seen: HashSet::new(),
+            channels: ChangeCounts::default(),
+            now: chrono::Utc::now().timestamp(),
+        })
+    }
     
-    db.query("DELETE FROM channel WHERE playlist_id = $playlist_id")
-        .bind(("playlist_id", playlist_id.clone()))
-        .await
-        .map_err(|e| format!("Failed to clear channels: {}", e))?;
+    /// Stage the next channels, in provider order
+    pub async fn add_channels(&mut self, channels: Vec<CachedChannel>) -> Result<(), String> {
+        let mut diff = batch::Diff::new();
+        let mut moved: Vec<(String, PositionRecord)> = Vec::new();
+        
+        for ch in channels {
+            let key = item_key(&self.playlist_id, &ch.content_type, &ch.id);
+            let position = self.seen.len() as i64;
+            if !self.seen.insert(key.clone()) {
+                return Err(self.fail(format!("Duplicate record key {}", key)).await);
+            }
+            let stored = self.existing.remove(&key);
+            // Without a provider date, keep the time the channel was first seen
+            let added_at = ch
+                .added_at
+                .or_else(|| stored.as_ref().and_then(|r| r.added_at))
+                .unwrap_or(self.now);
+            let record = ChannelRecord {
+                playlist_id: self.playlist_id.clone(),
+                category_id: ch.category_id,
+                name: ch.name,
+                url: ch.url,
+                logo: ch.logo,
+                group_title: ch.group_title,
+                content_type: ch.content_type,
+                stream_id: ch.stream_id,
+                container_extension: ch.container_extension,
+                tvg_id: ch.tvg_id,
+                tvg_shift: ch.tvg_shift,
+                channel_number: ch.channel_number,
+                rating: ch.rating,
+                added_at: Some(added_at),
+                position: Some(position),
+                attributes: ch.attributes,
+            };
+            if stored.as_ref().is_some_and(|old| *old == record && old.position != record.position) {
+                moved.push((key.clone(), PositionRecord { position: record.position }));
+            }
+            diff.push(key, record, stored);
+        }
+        
+        self.progress.grow(diff.writes() + moved.len());
+        let mut result = batch::stage(&self.playlist_id, "channel", &diff, &mut self.progress).await;
+        if result.is_ok() {
+            result = batch::stage_moves(&self.playlist_id, "channel", &moved, &mut self.progress).await;
+        }
+        if let Err(e) = result {
+            return Err(self.fail(e).await);
+        }
+        
+        let counts = diff.counts();
+        self.channels.added += counts.added;
+        self.channels.updated += counts.updated;
+        self.channels.unchanged += counts.unchanged;
+        Ok(())
+    }
     
-    // Insert categories
-    for cat in categories {
-        let record = CategoryRecord {
-            playlist_id: playlist_id.clone(),
-            name: cat.name,
-            content_type: cat.content_type,
+    /// Stage the categories and the removal of every channel no batch listed,
+    /// then apply all staged writes in a single transaction.
+    pub async fn finish(mut self, categories: Vec<CachedCategory>) -> Result<CacheSummary, String> {
+        let categories: Vec<(String, CategoryRecord)> = categories
+            .into_iter()
+            .map(|cat| {
+                let key = item_key(&self.playlist_id, &cat.content_type, &cat.id);
+                let record = CategoryRecord {
+                    playlist_id: self.playlist_id.clone(),
+                    name: cat.name,
+                    content_type: cat.content_type,
+                };
+                (key, record)
+            })
+            .collect();
+        
+        let category_diff = async {
+            let existing = batch::load_keyed::<CategoryRecord>("category", &self.playlist_id).await?;
+            batch::diff(existing, categories)
         };
-        let _: Option<CategoryRecord> = db
-            .create(("category", format!("{}_{}", playlist_id, cat.id)))
-            .content(record)
-            .await
-            .map_err(|e| format!("Failed to insert category: {}", e))?;
+        let category_diff = match category_diff.await {
+            Ok(diff) => diff,
+            Err(e) => return Err(self.fail(e).await),
+        };
+        let mut removed = batch::Diff::new();
+        removed.delete_rest(std::mem::take(&mut self.existing));
+        
+        self.progress.grow(category_diff.writes() + removed.writes());
+        let mut result = batch::stage(&self.playlist_id, "category", &category_diff, &mut self.progress).await;
+        if result.is_ok() {
+            result = batch::stage(&self.playlist_id, "channel", &removed, &mut self.progress).await;
+        }
+        if result.is_ok() {
+            self.progress.emit("committing");
+            result = batch::commit_stage(&self.playlist_id, &["category", "channel"]).await;
+        }
+        if let Err(e) = result {
+            return Err(self.fail(e).await);
+        }
+        self.progress.emit("completed");
+        
+        self.channels.removed = removed.counts().removed;
+        println!(
+            "Cached data for playlist {}: {} added, {} updated, {} removed channels",
+            self.playlist_id, self.channels.added, self.channels.updated, self.channels.removed
+        );
+        
+        Ok(CacheSummary {
+            playlist_id: self.playlist_id,
+            categories: category_diff.counts(),
+            channels: self.channels,
+        })
     }
     
-    // Insert channels
-    for ch in channels {
-        let record = ChannelRecord {
-            playlist_id: playlist_id.clone(),
-            category_id: ch.category_id,
-            name: ch.name,
-            url: ch.url,
-            logo: ch.logo,
-            group_title: ch.group_title,
-            content_type: ch.content_type,
-            stream_id: ch.stream_id,
-            container_extension: ch.container_extension,
-        };
-        let _: Option<ChannelRecord> = db
-            .create(("channel", format!("{}_{}", playlist_id, ch.id)))
-            .content(record)
-            .await
-            .map_err(|e| format!("Failed to insert channel: {}", e))?;
+    /// Drop everything staged so far, e.g. when the source failed to load
+    pub async fn discard(&mut self) {
+        if let Err(e) = batch::clear_stage(&self.playlist_id).await {
+            eprintln!("Failed to clear staged records of {}: {}", self.playlist_id, e);
+        }
+        self.progress.emit("rolled_back");
     }
     
-    println!("Cached data for playlist {}", playlist_id);
-    Ok(())
+    async fn fail(&mut self, error: String) -> String {
+        self.discard().await;
+        format!("Failed to cache playlist {}: {}", self.playlist_id, error)
+    }
+}
+
+/// Bring the cached categories and channels of a playlist in line with the
+/// given lists, touching only records whose stable id is new, changed or gone.
+pub async fn cache_playlist_data(
+    playlist_id: String,
+    categories: Vec<CachedCategory>,
+    channels: Vec<CachedChannel>,
+    on_progress: impl Fn(CacheProgress) + Send + Sync,
+) -> Result<CacheSummary, String> {
+    let mut writer = CacheWriter::start(playlist_id, &on_progress).await?;
+    
+    let mut channels = channels.into_iter();
+    loop {
+        let chunk: Vec<CachedChannel> = channels.by_ref().take(CACHE_CHUNK_SIZE).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        writer.add_channels(chunk).await?;
+    }
+    
+    writer.finish(categories).await
 }
 
+/// Categories of a playlist with the user's renames and order applied.
+/// Hidden categories are left out unless `include_hidden` is set.
 pub async fn get_cached_categories(
     playlist_id: String,
     content_type: String,
+    include_hidden: bool,
 ) -> Result<Vec<CachedCategory>, String> {
     let db = get_db().await?;
     
     let mut result = db
-        .query("SELECT * FROM category WHERE playlist_id = $playlist_id AND content_type = $content_type ORDER BY name")
-        .bind(("playlist_id", playlist_id))
-        .bind(("content_type", content_type))
+        .query("SELECT *, meta::id(id) AS key OMIT id FROM category WHERE playlist_id = $playlist_id AND content_type = $content_type ORDER BY name")
+        .bind(("playlist_id", playlist_id.clone()))
+        .bind(("content_type", content_type.clone()))
         .await
         .map_err(|e| format!("Failed to query categories: {}", e))?;
     
-    let records: Vec<CategoryRecord> = result
+    let records: Vec<Keyed<CategoryRecord>> = result
         .take(0)
         .map_err(|e| format!("Failed to parse categories: {}", e))?;
     
-    Ok(records
+    let categories = records
         .into_iter()
-        .enumerate()
-        .map(|(i, r)| CachedCategory {
-            id: format!("{}", i),
-            name: r.name,
-            content_type: r.content_type,
+        .map(|r| CachedCategory {
+            id: original_id(&playlist_id, &r.record.content_type, r.key),
+            name: r.record.name,
+            content_type: r.record.content_type,
         })
-        .collect())
+        .collect();
+    
+    group::apply_category_overrides(playlist_id, content_type, categories, include_hidden).await
 }
 
+/// Channels of a playlist, or of one of its categories. The whole-playlist
+/// list leaves out channels of hidden categories.
 pub async fn get_cached_channels(
     playlist_id: String,
     category_id: Option<String>,
@@ -205,10 +481,10 @@ pub async fn get_cached_channels(
 ) -> Result<Vec<CachedChannel>, String> {
     let db = get_db().await?;
     
-    let records: Vec<ChannelRecord> = if let Some(cat_id) = category_id {
+    let records: Vec<Keyed<ChannelRecord>> = if let Some(cat_id) = category_id {
         let mut result = db
-            .query("SELECT * FROM channel WHERE playlist_id = $playlist_id AND category_id = $category_id AND content_type = $content_type ORDER BY name")
-            .bind(("playlist_id", playlist_id))
+            .query("SELECT *, meta::id(id) AS key OMIT id FROM channel WHERE playlist_id = $playlist_id AND category_id = $category_id AND content_type = $content_type ORDER BY name")
+            .bind(("playlist_id", playlist_id.clone()))
             .bind(("category_id", cat_id))
             .bind(("content_type", content_type))
             .await
@@ -217,9 +493,13 @@ pub async fn get_cached_channels(
         result.take(0).map_err(|e| format!("Failed to parse channels: {}", e))?
     } else {
         let mut result = db
-            .query("SELECT * FROM channel WHERE playlist_id = $playlist_id AND content_type = $content_type ORDER BY name")
-            .bind(("playlist_id", playlist_id))
+            .query(format!(
+                "SELECT *, meta::id(id) AS key OMIT id FROM channel WHERE playlist_id = $playlist_id AND content_type = $content_type AND {} ORDER BY name",
+                group::NOT_HIDDEN
+            ))
+            .bind(("playlist_id", playlist_id.clone()))
             .bind(("content_type", content_type))
+            .bind(("hidden", group::hidden_categories(&playlist_id).await?))
             .await
             .map_err(|e| format!("Failed to query channels: {}", e))?;
         
@@ -228,21 +508,26 @@ pub async fn get_cached_channels(
     
     Ok(records
         .into_iter()
-        .enumerate()
-        .map(|(i, r)| CachedChannel {
-            id: format!("{}", i),
-            name: r.name,
-            url: r.url,
-            logo: r.logo,
-            group_title: r.group_title,
-            content_type: r.content_type,
-            category_id: r.category_id,
-            stream_id: r.stream_id,
-            container_extension: r.container_extension,
-        })
+        .map(|r| r.into_cached(&playlist_id))
         .collect())
 }
 
+/// A single cached channel, by the id its provider gave it
+pub async fn get_cached_channel(
+    playlist_id: String,
+    content_type: String,
+    channel_id: String,
+) -> Result<Option<CachedChannel>, String> {
+    let db = get_db().await?;
+    
+    let record: Option<ChannelRecord> = db
+        .select(("channel", item_key(&playlist_id, &content_type, &channel_id)))
+        .await
+        .map_err(|e| format!("Failed to get channel: {}", e))?;
+    
+    Ok(record.map(|r| r.into_cached(channel_id)))
+}
+
 pub async fn is_playlist_cached(playlist_id: String) -> Result<bool, String> {
     let db = get_db().await?;
     
@@ -270,59 +555,15 @@ pub async fn clear_playlist_cache(playlist_id: String) -> Result<(), String> {
         .map_err(|e| format!("Failed to clear channels: {}", e))?;
     
     db.query("DELETE FROM category WHERE playlist_id = $playlist_id")
-        .bind(("playlist_id", playlist_id))
+        .bind(("playlist_id", playlist_id.clone()))
         .await
         .map_err(|e| format!("Failed to clear categories: {}", e))?;
     
-    Ok(())
-}
-
-pub async fn search_cached_channels(
-    playlist_id: String,
-    query: String,
-    content_type: Option<String>,
-    limit: i32,
-) -> Result<Vec<CachedChannel>, String> {
-    let db = get_db().await?;
+    series::clear_series(playlist_id.clone()).await?;
+    vod::clear_vod_metadata(playlist_id.clone()).await?;
+    epg::clear_epg(playlist_id).await?;
     
-    let records: Vec<ChannelRecord> = if let Some(ct) = content_type {
-        let mut result = db
-            .query("SELECT * FROM channel WHERE playlist_id = $playlist_id AND content_type = $content_type AND string::lowercase(name) CONTAINS string::lowercase($query) ORDER BY name LIMIT $limit")
-            .bind(("playlist_id", playlist_id))
-            .bind(("content_type", ct))
-            .bind(("query", query))
-            .bind(("limit", limit))
-            .await
-            .map_err(|e| format!("Failed to search channels: {}", e))?;
-        
-        result.take(0).map_err(|e| format!("Failed to parse channels: {}", e))?
-    } else {
-        let mut result = db
-            .query("SELECT * FROM channel WHERE playlist_id = $playlist_id AND string::lowercase(name) CONTAINS string::lowercase($query) ORDER BY name LIMIT $limit")
-            .bind(("playlist_id", playlist_id))
-            .bind(("query", query))
-            .bind(("limit", limit))
-            .await
-            .map_err(|e| format!("Failed to search channels: {}", e))?;
-        
-        result.take(0).map_err(|e| format!("Failed to parse channels: {}", e))?
-    };
-    
-    Ok(records
-        .into_iter()
-        .enumerate()
-        .map(|(i, r)| CachedChannel {
-            id: format!("{}", i),
-            name: r.name,
-            url: r.url,
-            logo: r.logo,
-            group_title: r.group_title,
-            content_type: r.content_type,
-            category_id: r.category_id,
-            stream_id: r.stream_id,
-            container_extension: r.container_extension,
-        })
-        .collect())
+    Ok(())
 }
 
 pub async fn get_content_availability(playlist_id: String) -> Result<HashMap<String, bool>, String> {
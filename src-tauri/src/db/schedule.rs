@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use crate::types::ScheduledPlaylist;
+use super::get_db;
+
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS scheduled_playlist SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON scheduled_playlist TYPE string;
+    DEFINE FIELD IF NOT EXISTS kind ON scheduled_playlist TYPE string;
+    DEFINE FIELD IF NOT EXISTS url ON scheduled_playlist TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS account ON scheduled_playlist TYPE option<record<xtream_account>>;
+    DEFINE FIELD IF NOT EXISTS epg_urls ON scheduled_playlist TYPE array<string>;
+    DEFINE FIELD IF NOT EXISTS tvg_urls ON scheduled_playlist TYPE array<string>;
+    DEFINE FIELD IF NOT EXISTS interval_mins ON scheduled_playlist TYPE int;
+    DEFINE FIELD IF NOT EXISTS enabled ON scheduled_playlist TYPE bool;
+    DEFINE FIELD IF NOT EXISTS last_checked ON scheduled_playlist TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS last_changed ON scheduled_playlist TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS last_error ON scheduled_playlist TYPE option<string>;
+
+    DEFINE TABLE IF NOT EXISTS source_validator SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS etag ON source_validator TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS last_modified ON source_validator TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS content_hash ON source_validator TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS checked_at ON source_validator TYPE int;
+    -- Guide validators used to be keyed by URL, which can hold Xtream credentials
+    DELETE source_validator WHERE string::contains(meta::id(id), '://');
+";
+
+// Scheduled playlist record for SurrealDB. Xtream playlists link to their
+// account record; credentials are never stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ScheduledPlaylistRecord {
+    playlist_id: String,
+    kind: String,
+    url: Option<String>,
+    account: Option<RecordId>,
+    epg_urls: Vec<String>,
+    tvg_urls: Vec<String>,
+    interval_mins: i64,
+    enabled: bool,
+    last_checked: Option<i64>,
+    last_changed: Option<i64>,
+    last_error: Option<String>,
+}
+
+impl From<ScheduledPlaylistRecord> for ScheduledPlaylist {
+    fn from(r: ScheduledPlaylistRecord) -> Self {
+        ScheduledPlaylist {
+            playlist_id: r.playlist_id,
+            kind: r.kind,
+            url: r.url,
+            credentials: None,
+            epg_urls: r.epg_urls,
+            tvg_urls: r.tvg_urls,
+            interval_mins: r.interval_mins.max(0) as u32,
+            enabled: r.enabled,
+            last_checked: r.last_checked,
+            last_changed: r.last_changed,
+            last_error: r.last_error,
+        }
+    }
+}
+
+/// Validators remembered for a URL (or any other source key) between refreshes
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SourceValidator {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_hash: Option<String>,
+    pub checked_at: i64,
+}
+
+pub async fn save_scheduled_playlist(playlist: ScheduledPlaylist) -> Result<(), String> {
+    let db = get_db().await?;
+    let account = (playlist.kind == "xtream")
+        .then(|| RecordId::from_table_key("xtream_account", playlist.playlist_id.clone()));
+    
+    let record = ScheduledPlaylistRecord {
+        playlist_id: playlist.playlist_id.clone(),
+        kind: playlist.kind,
+        url: playlist.url,
+        account,
+        epg_urls: playlist.epg_urls,
+        tvg_urls: playlist.tvg_urls,
+        interval_mins: playlist.interval_mins as i64,
+        enabled: playlist.enabled,
+        last_checked: playlist.last_checked,
+        last_changed: playlist.last_changed,
+        last_error: playlist.last_error,
+    };
+    
+    let _: Option<ScheduledPlaylistRecord> = db
+        .upsert(("scheduled_playlist", playlist.playlist_id))
+        .content(record)
+        .await
+        .map_err(|e| format!("Failed to save scheduled playlist: {}", e))?;
+    
+    Ok(())
+}
+
+pub async fn get_scheduled_playlists() -> Result<Vec<ScheduledPlaylist>, String> {
+    let db = get_db().await?;
+    
+    let records: Vec<ScheduledPlaylistRecord> = db
+        .select("scheduled_playlist")
+        .await
+        .map_err(|e| format!("Failed to get scheduled playlists: {}", e))?;
+    
+    Ok(records.into_iter().map(ScheduledPlaylist::from).collect())
+}
+
+pub async fn get_scheduled_playlist(playlist_id: String) -> Result<Option<ScheduledPlaylist>, String> {
+    let db = get_db().await?;
+    
+    let record: Option<ScheduledPlaylistRecord> = db
+        .select(("scheduled_playlist", playlist_id))
+        .await
+        .map_err(|e| format!("Failed to get scheduled playlist: {}", e))?;
+    
+    Ok(record.map(ScheduledPlaylist::from))
+}
+
+pub async fn delete_scheduled_playlist(playlist_id: String) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let _: Option<ScheduledPlaylistRecord> = db
+        .delete(("scheduled_playlist", playlist_id))
+        .await
+        .map_err(|e| format!("Failed to delete scheduled playlist: {}", e))?;
+    
+    Ok(())
+}
+
+pub async fn get_source_validator(key: String) -> Result<Option<SourceValidator>, String> {
+    let db = get_db().await?;
+    
+    db.select(("source_validator", key))
+        .await
+        .map_err(|e| format!("Failed to get source validator: {}", e))
+}
+
+pub async fn save_source_validator(key: String, validator: SourceValidator) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    let _: Option<SourceValidator> = db
+        .upsert(("source_validator", key))
+        .content(validator)
+        .await
+        .map_err(|e| format!("Failed to save source validator: {}", e))?;
+    
+    Ok(())
+}
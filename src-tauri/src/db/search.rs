@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use surrealdb::RecordId;
+
+use crate::types::{
+    CachedChannel, ChannelSearchResult, ChannelSource, GlobalSearchHit, GlobalSearchResults, MatchSpan,
+    PlaylistSearchGroup,
+};
+use super::{get_db, group, item_key, original_id, ChannelRecord, Keyed};
+
+// Defined after the channel and vod_metadata tables so the indexes don't
+// create them schemaless
+pub(super) const SCHEMA: &str = "
+    DEFINE ANALYZER IF NOT EXISTS catalog_search TOKENIZERS blank, class, punct FILTERS ascii, lowercase, edgengram(1, 24);
+    DEFINE INDEX IF NOT EXISTS idx_channel_name_search ON channel FIELDS name SEARCH ANALYZER catalog_search BM25 HIGHLIGHTS;
+    DEFINE INDEX IF NOT EXISTS idx_channel_group_search ON channel FIELDS group_title SEARCH ANALYZER catalog_search BM25 HIGHLIGHTS;
+    DEFINE INDEX IF NOT EXISTS idx_vod_plot_search ON vod_metadata FIELDS plot SEARCH ANALYZER catalog_search BM25 HIGHLIGHTS;
+    DEFINE INDEX IF NOT EXISTS idx_vod_genre_search ON vod_metadata FIELDS genre SEARCH ANALYZER catalog_search BM25 HIGHLIGHTS;
+    DEFINE INDEX IF NOT EXISTS idx_vod_director_search ON vod_metadata FIELDS director SEARCH ANALYZER catalog_search BM25 HIGHLIGHTS;
+    DEFINE INDEX IF NOT EXISTS idx_vod_cast_search ON vod_metadata FIELDS cast SEARCH ANALYZER catalog_search BM25 HIGHLIGHTS;
+";
+
+/// Query words beyond this are ignored
+const MAX_TERMS: usize = 8;
+
+const CHANNEL_FIELDS: [&str; 2] = ["name", "group_title"];
+const METADATA_FIELDS: [&str; 4] = ["plot", "genre", "director", "cast"];
+
+/// Relevance per matched word: the name counts most, VOD metadata least
+const NAME_WEIGHT: f64 = 4.0;
+const GROUP_WEIGHT: f64 = 2.0;
+const METADATA_WEIGHT: f64 = 1.0;
+
+// Character range of a match, as returned by `search::offsets`
+#[derive(Debug, Deserialize)]
+struct Offset {
+    s: usize,
+    e: usize,
+}
+
+// `search::offsets` for every term, per field
+type FieldOffsets = Vec<Vec<Option<HashMap<String, Vec<Offset>>>>>;
+
+// A channel matched by name or group title
+#[derive(Debug, Deserialize)]
+struct ChannelHit {
+    #[serde(flatten)]
+    channel: Keyed<ChannelRecord>,
+    hits: Vec<usize>,
+    offsets: FieldOffsets,
+    score: f64,
+}
+
+// A channel matched through its VOD metadata
+#[derive(Debug, Deserialize)]
+struct MetadataHit {
+    channel_id: String,
+    content_type: String,
+    hits: Vec<usize>,
+    offsets: FieldOffsets,
+    score: f64,
+}
+
+/// Split a query into the words that are matched separately; a record
+/// matching more of them ranks higher.
+fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in query.split_whitespace() {
+        let word = word.to_lowercase();
+        if word.chars().any(char::is_alphanumeric) && !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms.truncate(MAX_TERMS);
+    terms
+}
+
+/// Build the match condition and the `hits`, `offsets` and `score`
+/// projections for `fields`, with one match reference per field and term.
+fn match_clause(fields: &[&str], terms: usize) -> (String, String) {
+    let mut conditions = Vec::new();
+    let mut hits = Vec::new();
+    let mut offsets = Vec::new();
+    let mut scores = Vec::new();
+    
+    for (f, field) in fields.iter().enumerate() {
+        let refs: Vec<usize> = (0..terms).map(|t| t * fields.len() + f).collect();
+        for (t, r) in refs.iter().enumerate() {
+            conditions.push(format!("{} @{}@ $term{}", field, r, t));
+            scores.push(format!("search::score({})", r));
+        }
+        hits.push(
+            refs.iter()
+                .map(|r| format!("count(search::offsets({}) != NONE)", r))
+                .collect::<Vec<_>>()
+                .join(" + "),
+        );
+        offsets.push(format!(
+            "[{}]",
+            refs.iter()
+                .map(|r| format!("search::offsets({})", r))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    
+    let projection = format!(
+        "[{}] AS hits, [{}] AS offsets, math::sum([{}]) AS score",
+        hits.join(", "),
+        offsets.join(", "),
+        scores.join(", ")
+    );
+    (format!("({})", conditions.join(" OR ")), projection)
+}
+
+fn term_bindings(terms: &[String]) -> HashMap<String, String> {
+    terms
+        .iter()
+        .enumerate()
+        .map(|(i, term)| (format!("term{}", i), term.clone()))
+        .collect()
+}
+
+fn spans(fields: &[&str], offsets: FieldOffsets, out: &mut Vec<MatchSpan>) {
+    for (field, per_term) in fields.iter().zip(offsets) {
+        for offsets in per_term.into_iter().flatten() {
+            for offset in offsets.into_values().flatten() {
+                let span = MatchSpan {
+                    field: field.to_string(),
+                    start: offset.s,
+                    end: offset.e,
+                };
+                if !out.contains(&span) {
+                    out.push(span);
+                }
+            }
+        }
+    }
+}
+
+/// Maps BM25 scores, which can be negative, into (0, 1) so they only
+/// break ties between records matching the same words.
+fn tie_breaker(score: f64) -> f64 {
+    0.5 + score.atan() / std::f64::consts::PI
+}
+
+async fn search_channel_fields(
+    playlist_id: &str,
+    terms: &[String],
+    content_type: Option<String>,
+    limit: i32,
+) -> Result<Vec<ChannelHit>, String> {
+    let db = get_db().await?;
+    let (condition, projection) = match_clause(&CHANNEL_FIELDS, terms.len());
+    let type_filter = if content_type.is_some() { "AND content_type = $content_type" } else { "" };
+    
+    let mut result = db
+        .query(format!(
+            "SELECT *, meta::id(id) AS key, {} OMIT id FROM channel WHERE playlist_id = $playlist_id {} AND {} AND {} ORDER BY hits DESC, score DESC, name LIMIT $limit",
+            projection, type_filter, group::NOT_HIDDEN, condition
+        ))
+        .bind(("playlist_id", playlist_id.to_string()))
+        .bind(("hidden", group::hidden_categories(playlist_id).await?))
+        .bind(("content_type", content_type))
+        .bind(("limit", limit))
+        .bind(term_bindings(terms))
+        .await
+        .map_err(|e| format!("Failed to search channels: {}", e))?;
+    
+    result.take(0).map_err(|e| format!("Failed to parse channels: {}", e))
+}
+
+async fn search_metadata_fields(
+    playlist_id: &str,
+    terms: &[String],
+    content_type: Option<String>,
+    limit: i32,
+) -> Result<Vec<MetadataHit>, String> {
+    let db = get_db().await?;
+    let (condition, projection) = match_clause(&METADATA_FIELDS, terms.len());
+    let type_filter = if content_type.is_some() { "AND content_type = $content_type" } else { "" };
+    
+    let mut result = db
+        .query(format!(
+            "SELECT channel_id, content_type, {} FROM vod_metadata WHERE playlist_id = $playlist_id {} AND {} ORDER BY hits DESC, score DESC LIMIT $limit",
+            projection, type_filter, condition
+        ))
+        .bind(("playlist_id", playlist_id.to_string()))
+        .bind(("content_type", content_type))
+        .bind(("limit", limit))
+        .bind(term_bindings(terms))
+        .await
+        .map_err(|e| format!("Failed to search VOD metadata: {}", e))?;
+    
+    result.take(0).map_err(|e| format!("Failed to parse VOD metadata: {}", e))
+}
+
+/// Cached channels of a playlist outside hidden categories, by content type
+/// and channel id
+async fn get_channel_records(
+    playlist_id: &str,
+    channels: Vec<(String, String)>,
+) -> Result<Vec<Keyed<ChannelRecord>>, String> {
+    let db = get_db().await?;
+    
+    let records: Vec<RecordId> = channels
+        .iter()
+        .map(|(content_type, id)| RecordId::from_table_key("channel", item_key(playlist_id, content_type, id)))
+        .collect();
+    
+    let mut result = db
+        .query(format!("SELECT *, meta::id(id) AS key OMIT id FROM $records WHERE {}", group::NOT_HIDDEN))
+        .bind(("records", records))
+        .bind(("hidden", group::hidden_categories(playlist_id).await?))
+        .await
+        .map_err(|e| format!("Failed to query channels: {}", e))?;
+    
+    result.take(0).map_err(|e| format!("Failed to parse channels: {}", e))
+}
+
+/// Full-text search over channel names, group titles and cached VOD
+/// metadata, best matches first. Each query word is matched as a prefix,
+/// ignoring case and accents. Channels of hidden categories are left out.
+pub async fn search_cached_channels(
+    playlist_id: String,
+    query: String,
+    content_type: Option<String>,
+    limit: i32,
+) -> Result<Vec<ChannelSearchResult>, String> {
+    let terms = query_terms(&query);
+    if terms.is_empty() || limit <= 0 {
+        return Ok(Vec::new());
+    }
+    
+    let channel_hits = search_channel_fields(&playlist_id, &terms, content_type.clone(), limit).await?;
+    
+    let mut results: Vec<ChannelSearchResult> = Vec::new();
+    let mut positions: HashMap<(String, String), usize> = HashMap::new();
+    
+    for hit in channel_hits {
+        let channel = hit.channel.into_cached(&playlist_id);
+        let mut highlights = Vec::new();
+        spans(&CHANNEL_FIELDS, hit.offsets, &mut highlights);
+        let matched = NAME_WEIGHT * hit.hits[0] as f64 + GROUP_WEIGHT * hit.hits[1] as f64;
+    
+        positions.insert((channel.content_type.clone(), channel.id.clone()), results.len());
+        results.push(ChannelSearchResult {
+            channel,
+            score: matched + tie_breaker(hit.score),
+            highlights,
+        });
+    }
+    
+    let metadata_hits = search_metadata_fields(&playlist_id, &terms, content_type, limit).await?;
+    let mut pending: Vec<(MetadataHit, f64)> = Vec::new();
+    
+    for hit in metadata_hits {
+        let matched = METADATA_WEIGHT * hit.hits.iter().sum::<usize>() as f64;
+        match positions.get(&(hit.content_type.clone(), hit.channel_id.clone())) {
+            Some(&i) => {
+                results[i].score += matched;
+                spans(&METADATA_FIELDS, hit.offsets, &mut results[i].highlights);
+            }
+            None => pending.push((hit, matched)),
+        }
+    }
+    
+    let keys: Vec<(String, String)> = pending
+        .iter()
+        .map(|(hit, _)| (hit.content_type.clone(), hit.channel_id.clone()))
+        .collect();
+    let mut channels: HashMap<(String, String), ChannelRecord> = get_channel_records(&playlist_id, keys)
+        .await?
+        .into_iter()
+        .map(|r| {
+            let id = original_id(&playlist_id, &r.record.content_type, r.key);
+            ((r.record.content_type.clone(), id), r.record)
+        })
+        .collect();
+    
+    for (hit, matched) in pending {
+        let Some(record) = channels.remove(&(hit.content_type, hit.channel_id.clone())) else {
+            continue;
+        };
+        let mut highlights = Vec::new();
+        spans(&METADATA_FIELDS, hit.offsets, &mut highlights);
+        results.push(ChannelSearchResult {
+            channel: record.into_cached(hit.channel_id),
+            score: matched + tie_breaker(hit.score),
+            highlights,
+        });
+    }
+    
+    results.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.channel.name.cmp(&b.channel.name))
+    });
+    results.truncate(limit as usize);
+    
+    Ok(results)
+}
+
+/// Ids of every playlist with cached categories
+async fn cached_playlist_ids() -> Result<Vec<String>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("RETURN array::sort(array::distinct(SELECT VALUE playlist_id FROM category))")
+        .await
+        .map_err(|e| format!("Failed to query playlists: {}", e))?;
+    
+    result.take(0).map_err(|e| format!("Failed to parse playlists: {}", e))
+}
+
+/// What makes two channels from different playlists "the same": the EPG id
+/// when there is one, otherwise the name without case, spacing or punctuation.
+fn duplicate_key(channel: &CachedChannel) -> String {
+    let identity = match channel.tvg_id.as_deref().map(str::trim).filter(|id| !id.is_empty()) {
+        Some(tvg_id) => format!("tvg:{}", tvg_id.to_lowercase()),
+        None => format!(
+            "name:{}",
+            channel
+                .name
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        ),
+    };
+    format!("{}|{}", channel.content_type, identity)
+}
+
+/// Search every cached playlist at once. A channel offered by several
+/// playlists is listed once, under the playlist where it matched best, with
+/// the other copies in `also_in`. Copies within one playlist, e.g. HD and SD
+/// variants, stay separate results.
+pub async fn search_all_playlists(
+    query: String,
+    content_type: Option<String>,
+    limit: i32,
+) -> Result<GlobalSearchResults, String> {
+    let mut hits: Vec<(String, ChannelSearchResult)> = Vec::new();
+    let mut returned: HashMap<String, usize> = HashMap::new();
+    let playlist_ids = cached_playlist_ids().await?;
+    
+    for playlist_id in &playlist_ids {
+        let results = search_cached_channels(playlist_id.clone(), query.clone(), content_type.clone(), limit).await?;
+        returned.insert(playlist_id.clone(), results.len());
+        hits.extend(results.into_iter().map(|r| (playlist_id.clone(), r)));
+    }
+    
+    // Best copy of each channel first, so it becomes the one that is kept
+    hits.sort_by(|(a_playlist, a), (b_playlist, b)| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.channel.name.cmp(&b.channel.name))
+            .then_with(|| a_playlist.cmp(b_playlist))
+    });
+    
+    let mut kept: Vec<(String, GlobalSearchHit)> = Vec::new();
+    let mut positions: HashMap<String, Vec<usize>> = HashMap::new();
+    
+    for (playlist_id, result) in hits {
+        let key = duplicate_key(&result.channel);
+        // A kept result that has no copy from this playlist yet
+        let merge_into = positions.get(&key).and_then(|indexes| {
+            indexes.iter().copied().find(|&i| {
+                kept[i].0 != playlist_id && !kept[i].1.also_in.iter().any(|s| s.playlist_id == playlist_id)
+            })
+        });
+        match merge_into {
+            Some(i) => kept[i].1.also_in.push(ChannelSource {
+                playlist_id,
+                content_type: result.channel.content_type,
+                channel_id: result.channel.id,
+            }),
+            None => {
+                if kept.len() >= limit.max(0) as usize {
+                    continue;
+                }
+                positions.entry(key).or_default().push(kept.len());
+                kept.push((playlist_id, GlobalSearchHit { result, also_in: Vec::new() }));
+            }
+        }
+    }
+    
+    let total = kept.len();
+    let mut groups: Vec<PlaylistSearchGroup> = playlist_ids
+        .into_iter()
+        .map(|playlist_id| PlaylistSearchGroup {
+            returned: returned.get(&playlist_id).copied().unwrap_or(0),
+            playlist_id,
+            results: Vec::new(),
+        })
+        .collect();
+    for (playlist_id, hit) in kept {
+        if let Some(group) = groups.iter_mut().find(|g| g.playlist_id == playlist_id) {
+            group.results.push(hit);
+        }
+    }
+    groups.retain(|g| g.returned > 0);
+    
+    Ok(GlobalSearchResults { total, groups })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::{block_on, category, channel};
+    use crate::db::{cache_playlist_data, save_vod_metadata};
+    use crate::types::{CachedCategory, VodMetadata};
+
+    fn movie(id: &str, name: &str) -> CachedChannel {
+        CachedChannel {
+            content_type: "movie".to_string(),
+            ..channel(id, name, "films")
+        }
+    }
+
+    fn plot_and_cast(channel_id: &str, plot: &str, cast: &str) -> VodMetadata {
+        VodMetadata {
+            playlist_id: "search-ranking".to_string(),
+            channel_id: channel_id.to_string(),
+            content_type: "movie".to_string(),
+            plot: Some(plot.to_string()),
+            genre: None,
+            director: None,
+            cast: Some(cast.to_string()),
+            rating: None,
+            duration_secs: None,
+            release_date: None,
+            cover: None,
+            backdrop: None,
+            tmdb_id: None,
+            youtube_trailer: None,
+            fetched_at: 0,
+        }
+    }
+
+    // Shared by the tests, which run in parallel, so it is cached once
+    async fn cache_catalog() {
+        static CACHED: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+        CACHED.get_or_init(cache_catalog_once).await;
+    }
+
+    async fn cache_catalog_once() {
+        let categories = vec![
+            CachedCategory { content_type: "movie".to_string(), ..category("films", "Films") },
+            category("news", "News"),
+        ];
+        let channels = vec![
+            movie("1", "Casablanca"),
+            movie("2", "La Casa de Papel"),
+            movie("3", "Heist"),
+            channel("4", "Télé Casa", "news"),
+        ];
+        cache_playlist_data("search-ranking".into(), categories, channels, |_| {}).await.unwrap();
+        save_vod_metadata(plot_and_cast("3", "A crew robs the mint", "Úrsula Corberó")).await.unwrap();
+    }
+
+    fn ids(results: &[ChannelSearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.channel.id.as_str()).collect()
+    }
+
+    #[test]
+    fn query_terms_are_lowercased_and_deduplicated() {
+        assert_eq!(query_terms("La  CASA la - casa"), ["la", "casa"]);
+        assert!(query_terms(" - ").is_empty());
+    }
+
+    #[test]
+    fn every_matched_word_ranks_higher() {
+        block_on(async {
+            cache_catalog().await;
+            let results = search_cached_channels("search-ranking".into(), "la casa".into(), Some("movie".into()), 10)
+                .await
+                .unwrap();
+            assert_eq!(ids(&results), ["2", "1"]);
+            assert!(results[0].score > results[1].score + 1.0);
+        });
+    }
+
+    #[test]
+    fn accents_are_ignored() {
+        block_on(async {
+            cache_catalog().await;
+            let results = search_cached_channels("search-ranking".into(), "tele".into(), None, 10).await.unwrap();
+            assert_eq!(ids(&results), ["4"]);
+            let results = search_cached_channels("search-ranking".into(), "TÉLÉ".into(), None, 10).await.unwrap();
+            assert_eq!(ids(&results), ["4"]);
+            let results = search_cached_channels("search-ranking".into(), "ursula".into(), None, 10).await.unwrap();
+            assert_eq!(ids(&results), ["3"]);
+        });
+    }
+
+    #[test]
+    fn highlights_cover_the_matched_words() {
+        block_on(async {
+            cache_catalog().await;
+            let results = search_cached_channels("search-ranking".into(), "la casa".into(), Some("movie".into()), 10)
+                .await
+                .unwrap();
+            let name = "La Casa de Papel";
+            let mut matched: Vec<&str> = results[0]
+                .highlights
+                .iter()
+                .filter(|span| span.field == "name")
+                .map(|span| &name[span.start..span.end])
+                .collect();
+            matched.sort();
+            assert_eq!(matched, ["Casa", "La"]);
+
+            let results = search_cached_channels("search-ranking".into(), "mint".into(), None, 10).await.unwrap();
+            assert_eq!(ids(&results), ["3"]);
+            let span = &results[0].highlights[0];
+            assert_eq!(span.field, "plot");
+            assert_eq!(&"A crew robs the mint"[span.start..span.end], "mint");
+        });
+    }
+
+    #[test]
+    fn copies_from_other_playlists_are_merged() {
+        block_on(async {
+            let with_tvg_id = |id: &str, name: &str, tvg_id: Option<&str>| CachedChannel {
+                tvg_id: tvg_id.map(str::to_string),
+                ..channel(id, name, "news")
+            };
+            let first = vec![
+                with_tvg_id("a", "Quorvex One HD", Some("quorvex1.uk")),
+                with_tvg_id("b", "Quorvex One SD", Some("quorvex1.uk")),
+                with_tvg_id("c", "Quorvex Two", None),
+            ];
+            let second = vec![
+                with_tvg_id("d", "Quorvex One", Some("Quorvex1.uk")),
+                with_tvg_id("e", "QUORVEX two!", None),
+                with_tvg_id("f", "Quorvex Three", None),
+            ];
+            cache_playlist_data("search-global-a".into(), vec![category("news", "News")], first, |_| {}).await.unwrap();
+            cache_playlist_data("search-global-b".into(), vec![category("news", "News")], second, |_| {}).await.unwrap();
+
+            let found = search_all_playlists("quorvex".into(), None, 10).await.unwrap();
+            assert_eq!(found.total, 4);
+            assert_eq!(found.groups.len(), 2);
+            for group in &found.groups {
+                // Every match counts for its playlist, wherever it is listed
+                assert_eq!(group.returned, 3);
+                for hit in &group.results {
+                    assert!(hit.also_in.iter().all(|s| s.playlist_id != group.playlist_id));
+                }
+            }
+
+            let hits: Vec<&GlobalSearchHit> = found.groups.iter().flat_map(|g| &g.results).collect();
+            assert_eq!(hits.len(), 4);
+            // The HD and SD variants stay apart, and only one of them lists the other playlist
+            let one = hits.iter().filter(|h| h.result.channel.tvg_id.is_some()).collect::<Vec<_>>();
+            assert_eq!(one.iter().map(|h| h.also_in.len()).sum::<usize>(), 1);
+            let two = hits.iter().find(|h| h.result.channel.name.to_lowercase().starts_with("quorvex two")).unwrap();
+            assert_eq!(two.also_in.len(), 1);
+        });
+    }
+}
@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use crate::types::{Episode, Season, Series, SeriesDetails};
+use super::batch::Row;
+use super::get_db;
+
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS series SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON series TYPE string;
+    DEFINE FIELD IF NOT EXISTS series_id ON series TYPE string;
+    DEFINE FIELD IF NOT EXISTS name ON series TYPE string;
+    DEFINE FIELD IF NOT EXISTS cover ON series TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS plot ON series TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS cast ON series TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS director ON series TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS genre ON series TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS release_date ON series TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS rating ON series TYPE option<float>;
+    DEFINE FIELD IF NOT EXISTS backdrop ON series TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS tmdb_id ON series TYPE option<string>;
+    DEFINE INDEX IF NOT EXISTS idx_series_playlist ON series FIELDS playlist_id;
+
+    DEFINE TABLE IF NOT EXISTS season SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS series ON season TYPE record<series>;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON season TYPE string;
+    DEFINE FIELD IF NOT EXISTS season_number ON season TYPE int;
+    DEFINE FIELD IF NOT EXISTS name ON season TYPE string;
+    DEFINE FIELD IF NOT EXISTS overview ON season TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS air_date ON season TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS cover ON season TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS episode_count ON season TYPE int;
+    DEFINE INDEX IF NOT EXISTS idx_season_series ON season FIELDS series;
+    DEFINE INDEX IF NOT EXISTS idx_season_playlist ON season FIELDS playlist_id;
+
+    DEFINE TABLE IF NOT EXISTS episode SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS season ON episode TYPE record<season>;
+    DEFINE FIELD IF NOT EXISTS series ON episode TYPE record<series>;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON episode TYPE string;
+    DEFINE FIELD IF NOT EXISTS episode_id ON episode TYPE string;
+    DEFINE FIELD IF NOT EXISTS season_number ON episode TYPE int;
+    DEFINE FIELD IF NOT EXISTS episode_num ON episode TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS title ON episode TYPE string;
+    DEFINE FIELD IF NOT EXISTS url ON episode TYPE string;
+    DEFINE FIELD IF NOT EXISTS container_extension ON episode TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS plot ON episode TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS cover ON episode TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS duration_secs ON episode TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS rating ON episode TYPE option<float>;
+    DEFINE FIELD IF NOT EXISTS release_date ON episode TYPE option<string>;
+    DEFINE INDEX IF NOT EXISTS idx_episode_season ON episode FIELDS season;
+    DEFINE INDEX IF NOT EXISTS idx_episode_series ON episode FIELDS series, season_number;
+    DEFINE INDEX IF NOT EXISTS idx_episode_playlist ON episode FIELDS playlist_id;
+";
+
+// Series record for SurrealDB
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SeriesRecord {
+    playlist_id: String,
+    series_id: String,
+    name: String,
+    cover: Option<String>,
+    plot: Option<String>,
+    cast: Option<String>,
+    director: Option<String>,
+    genre: Option<String>,
+    release_date: Option<String>,
+    rating: Option<f64>,
+    backdrop: Option<String>,
+    tmdb_id: Option<String>,
+}
+
+// Season record for SurrealDB, linked to its series
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SeasonRecord {
+    series: RecordId,
+    playlist_id: String,
+    season_number: i64,
+    name: String,
+    overview: Option<String>,
+    air_date: Option<String>,
+    cover: Option<String>,
+    episode_count: i64,
+}
+
+// Episode record for SurrealDB, linked to its season and series
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct EpisodeRecord {
+    season: RecordId,
+    series: RecordId,
+    playlist_id: String,
+    episode_id: String,
+    season_number: i64,
+    episode_num: Option<i64>,
+    title: String,
+    url: String,
+    container_extension: Option<String>,
+    plot: Option<String>,
+    cover: Option<String>,
+    duration_secs: Option<i64>,
+    rating: Option<f64>,
+    release_date: Option<String>,
+}
+
+fn series_key(playlist_id: &str, series_id: &str) -> RecordId {
+    RecordId::from_table_key("series", format!("{}_{}", playlist_id, series_id))
+}
+
+fn season_key(playlist_id: &str, series_id: &str, season_number: i64) -> RecordId {
+    RecordId::from_table_key("season", format!("{}_{}_{}", playlist_id, series_id, season_number))
+}
+
+/// Replace the cached seasons and episodes of a series. Everything is
+/// written in one transaction, so a failure keeps the previous details.
+pub async fn cache_series_details(details: SeriesDetails) -> Result<(), String> {
+    let db = get_db().await?;
+    let playlist_id = details.series.playlist_id.clone();
+    let series_id = details.series.id.clone();
+    let series = series_key(&playlist_id, &series_id);
+    
+    let s = details.series;
+    let record = SeriesRecord {
+        playlist_id: playlist_id.clone(),
+        series_id: series_id.clone(),
+        name: s.name,
+        cover: s.cover,
+        plot: s.plot,
+        cast: s.cast,
+        director: s.director,
+        genre: s.genre,
+        release_date: s.release_date,
+        rating: s.rating,
+        backdrop: s.backdrop,
+        tmdb_id: s.tmdb_id,
+    };
+    
+    let seasons: Vec<Row<SeasonRecord>> = details
+        .seasons
+        .into_iter()
+        .map(|season| Row {
+            id: season_key(&playlist_id, &series_id, season.season_number),
+            record: SeasonRecord {
+                series: series.clone(),
+                playlist_id: playlist_id.clone(),
+                season_number: season.season_number,
+                name: season.name,
+                overview: season.overview,
+                air_date: season.air_date,
+                cover: season.cover,
+                episode_count: season.episode_count,
+            },
+        })
+        .collect();
+    
+    let episodes: Vec<Row<EpisodeRecord>> = details
+        .episodes
+        .into_iter()
+        .map(|ep| Row {
+            id: RecordId::from_table_key("episode", format!("{}_{}", playlist_id, ep.id)),
+            record: EpisodeRecord {
+                season: season_key(&playlist_id, &series_id, ep.season_number),
+                series: series.clone(),
+                playlist_id: playlist_id.clone(),
+                episode_id: ep.id,
+                season_number: ep.season_number,
+                episode_num: ep.episode_num,
+                title: ep.title,
+                url: ep.url,
+                container_extension: ep.container_extension,
+                plot: ep.plot,
+                cover: ep.cover,
+                duration_secs: ep.duration_secs,
+                rating: ep.rating,
+                release_date: ep.release_date,
+            },
+        })
+        .collect();
+    
+    // A duplicate season or episode fails the INSERT and cancels everything
+    db.query(
+        "BEGIN TRANSACTION;
+        DELETE FROM episode WHERE series = $series;
+        DELETE FROM season WHERE series = $series;
+        UPSERT $series CONTENT $record;
+        INSERT INTO season $seasons;
+        INSERT INTO episode $episodes;
+        COMMIT TRANSACTION;",
+    )
+    .bind(("series", series))
+    .bind(("record", record))
+    .bind(("seasons", seasons))
+    .bind(("episodes", episodes))
+    .await
+    .map_err(|e| format!("Failed to cache series {}: {}", series_id, e))?
+    .check()
+    .map_err(|e| format!("Failed to cache series {}: {}", series_id, e))?;
+    
+    println!("Cached series {} for playlist {}", series_id, playlist_id);
+    Ok(())
+}
+
+pub async fn get_cached_series(
+    playlist_id: String,
+    series_id: String,
+) -> Result<Option<Series>, String> {
+    let db = get_db().await?;
+    
+    let result: Option<SeriesRecord> = db
+        .select(series_key(&playlist_id, &series_id))
+        .await
+        .map_err(|e| format!("Failed to get series: {}", e))?;
+    
+    Ok(result.map(|r| Series {
+        id: r.series_id,
+        playlist_id: r.playlist_id,
+        name: r.name,
+        cover: r.cover,
+        plot: r.plot,
+        cast: r.cast,
+        director: r.director,
+        genre: r.genre,
+        release_date: r.release_date,
+        rating: r.rating,
+        backdrop: r.backdrop,
+        tmdb_id: r.tmdb_id,
+    }))
+}
+
+pub async fn get_cached_seasons(
+    playlist_id: String,
+    series_id: String,
+) -> Result<Vec<Season>, String> {
+    let db = get_db().await?;
+    
+    let mut result = db
+        .query("SELECT * FROM season WHERE series = $series ORDER BY season_number")
+        .bind(("series", series_key(&playlist_id, &series_id)))
+        .await
+        .map_err(|e| format!("Failed to query seasons: {}", e))?;
+    
+    let records: Vec<SeasonRecord> = result
+        .take(0)
+        .map_err(|e| format!("Failed to parse seasons: {}", e))?;
+    
+    Ok(records
+        .into_iter()
+        .map(|r| Season {
+            series_id: series_id.clone(),
+            season_number: r.season_number,
+            name: r.name,
+            overview: r.overview,
+            air_date: r.air_date,
+            cover: r.cover,
+            episode_count: r.episode_count,
+        })
+        .collect())
+}
+
+pub async fn get_cached_episodes(
+    playlist_id: String,
+    series_id: String,
+    season_number: Option<i64>,
+) -> Result<Vec<Episode>, String> {
+    let db = get_db().await?;
+    let series = series_key(&playlist_id, &series_id);
+    
+    let records: Vec<EpisodeRecord> = if let Some(season) = season_number {
+        let mut result = db
+            .query("SELECT * FROM episode WHERE season = $season ORDER BY episode_num")
+            .bind(("season", season_key(&playlist_id, &series_id, season)))
+            .await
+            .map_err(|e| format!("Failed to query episodes: {}", e))?;
+        
+        result.take(0).map_err(|e| format!("Failed to parse episodes: {}", e))?
+    } else {
+        let mut result = db
+            .query("SELECT * FROM episode WHERE series = $series ORDER BY season_number, episode_num")
+            .bind(("series", series))
+            .await
+            .map_err(|e| format!("Failed to query episodes: {}", e))?;
+        
+        result.take(0).map_err(|e| format!("Failed to parse episodes: {}", e))?
+    };
+    
+    Ok(records
+        .into_iter()
+        .map(|r| Episode {
+            id: r.episode_id,
+            series_id: series_id.clone(),
+            season_number: r.season_number,
+            episode_num: r.episode_num,
+            title: r.title,
+            url: r.url,
+            container_extension: r.container_extension,
+            plot: r.plot,
+            cover: r.cover,
+            duration_secs: r.duration_secs,
+            rating: r.rating,
+            release_date: r.release_date,
+        })
+        .collect())
+}
+
+/// Remove every cached series, season and episode of a playlist.
+pub(super) async fn clear_series(playlist_id: String) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    db.query("DELETE FROM episode WHERE playlist_id = $playlist_id; DELETE FROM season WHERE playlist_id = $playlist_id; DELETE FROM series WHERE playlist_id = $playlist_id;")
+        .bind(("playlist_id", playlist_id))
+        .await
+        .map_err(|e| format!("Failed to clear series: {}", e))?;
+    
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::sync::LazyLock;
+use tokio::runtime::Runtime;
+
+use crate::types::{CachedCategory, CachedChannel};
+
+// The database's background tasks live on the runtime that opened it, so
+// every test runs on this one instead of its own. Opening it here, once,
+// also keeps concurrent tests from each creating a database.
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start test runtime");
+    runtime.block_on(super::init_db()).expect("Failed to open test database");
+    runtime
+});
+
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    RUNTIME.block_on(future)
+}
+
+pub fn category(id: &str, name: &str) -> CachedCategory {
+    CachedCategory {
+        id: id.to_string(),
+        name: name.to_string(),
+        content_type: "live".to_string(),
+    }
+}
+
+pub fn channel(id: &str, name: &str, category_id: &str) -> CachedChannel {
+    CachedChannel {
+        id: id.to_string(),
+        name: name.to_string(),
+        url: format!("http://example.com/{}", id),
+        logo: None,
+        group_title: Some(category_id.to_string()),
+        content_type: "live".to_string(),
+        category_id: Some(category_id.to_string()),
+        stream_id: None,
+        container_extension: None,
+        tvg_id: None,
+        tvg_shift: None,
+        channel_number: None,
+        rating: None,
+        added_at: None,
+        attributes: Default::default(),
+    }
+}
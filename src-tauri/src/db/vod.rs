@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+
+use crate::types::VodMetadata;
+use super::{get_db, item_key};
+
+pub(super) const SCHEMA: &str = "
+    DEFINE TABLE IF NOT EXISTS vod_metadata SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS channel ON vod_metadata TYPE record<channel>;
+    DEFINE FIELD IF NOT EXISTS playlist_id ON vod_metadata TYPE string;
+    DEFINE FIELD IF NOT EXISTS channel_id ON vod_metadata TYPE string;
+    DEFINE FIELD IF NOT EXISTS content_type ON vod_metadata TYPE string;
+    DEFINE FIELD IF NOT EXISTS plot ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS genre ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS director ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS cast ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS rating ON vod_metadata TYPE option<float>;
+    DEFINE FIELD IF NOT EXISTS duration_secs ON vod_metadata TYPE option<int>;
+    DEFINE FIELD IF NOT EXISTS release_date ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS cover ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS backdrop ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS tmdb_id ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS youtube_trailer ON vod_metadata TYPE option<string>;
+    DEFINE FIELD IF NOT EXISTS fetched_at ON vod_metadata TYPE int;
+    DEFINE INDEX IF NOT EXISTS idx_vod_metadata_playlist ON vod_metadata FIELDS playlist_id;
+";
+
+// VOD metadata record for SurrealDB, linked to its channel record
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct VodMetadataRecord {
+    channel: RecordId,
+    playlist_id: String,
+    channel_id: String,
+    content_type: String,
+    plot: Option<String>,
+    genre: Option<String>,
+    director: Option<String>,
+    cast: Option<String>,
+    rating: Option<f64>,
+    duration_secs: Option<i64>,
+    release_date: Option<String>,
+    cover: Option<String>,
+    backdrop: Option<String>,
+    tmdb_id: Option<String>,
+    youtube_trailer: Option<String>,
+    fetched_at: i64,
+}
+
+pub async fn save_vod_metadata(metadata: VodMetadata) -> Result<(), String> {
+    let db = get_db().await?;
+    let key = item_key(&metadata.playlist_id, &metadata.content_type, &metadata.channel_id);
+    
+    let record = VodMetadataRecord {
+        channel: RecordId::from_table_key("channel", key.clone()),
+        playlist_id: metadata.playlist_id,
+        channel_id: metadata.channel_id,
+        content_type: metadata.content_type,
+        plot: metadata.plot,
+        genre: metadata.genre,
+        director: metadata.director,
+        cast: metadata.cast,
+        rating: metadata.rating,
+        duration_secs: metadata.duration_secs,
+        release_date: metadata.release_date,
+        cover: metadata.cover,
+        backdrop: metadata.backdrop,
+        tmdb_id: metadata.tmdb_id,
+        youtube_trailer: metadata.youtube_trailer,
+        fetched_at: metadata.fetched_at,
+    };
+    
+    let _: Option<VodMetadataRecord> = db
+        .upsert(("vod_metadata", key))
+        .content(record)
+        .await
+        .map_err(|e| format!("Failed to save VOD metadata: {}", e))?;
+    
+    Ok(())
+}
+
+pub async fn get_vod_metadata(
+    playlist_id: String,
+    content_type: String,
+    channel_id: String,
+) -> Result<Option<VodMetadata>, String> {
+    let db = get_db().await?;
+    
+    let result: Option<VodMetadataRecord> = db
+        .select(("vod_metadata", item_key(&playlist_id, &content_type, &channel_id)))
+        .await
+        .map_err(|e| format!("Failed to get VOD metadata: {}", e))?;
+    
+    Ok(result.map(|r| VodMetadata {
+        playlist_id: r.playlist_id,
+        channel_id: r.channel_id,
+        content_type: r.content_type,
+        plot: r.plot,
+        genre: r.genre,
+        director: r.director,
+        cast: r.cast,
+        rating: r.rating,
+        duration_secs: r.duration_secs,
+        release_date: r.release_date,
+        cover: r.cover,
+        backdrop: r.backdrop,
+        tmdb_id: r.tmdb_id,
+        youtube_trailer: r.youtube_trailer,
+        fetched_at: r.fetched_at,
+    }))
+}
+
+pub(super) async fn clear_vod_metadata(playlist_id: String) -> Result<(), String> {
+    let db = get_db().await?;
+    
+    db.query("DELETE FROM vod_metadata WHERE playlist_id = $playlist_id")
+        .bind(("playlist_id", playlist_id))
+        .await
+        .map_err(|e| format!("Failed to clear VOD metadata: {}", e))?;
+    
+    Ok(())
+}
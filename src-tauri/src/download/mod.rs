@@ -5,7 +5,7 @@ use tokio::io::AsyncWriteExt;
 use futures_util::StreamExt;
 use tauri::Emitter;
 
-use crate::types::{DownloadProgress, DownloadedItem};
+use crate::types::{DownloadProgress, DownloadedItem, StreamRequestOptions};
 
 pub struct DownloadState {
     pub active_downloads: Mutex<HashMap<String, bool>>,
@@ -32,6 +32,24 @@ pub fn get_downloads_path() -> Result<String, String> {
     Ok(dir.to_string_lossy().to_string())
 }
 
+/// Apply playlist-supplied User-Agent, Referer and extra headers to a request
+pub fn apply_request_options(
+    request: reqwest::RequestBuilder,
+    options: &StreamRequestOptions,
+) -> reqwest::RequestBuilder {
+    let mut request = request.header(
+        "User-Agent",
+        options.user_agent.as_deref().unwrap_or("WatchTV/1.0"),
+    );
+    if let Some(referrer) = &options.referrer {
+        request = request.header("Referer", referrer.as_str());
+    }
+    for (key, value) in &options.headers {
+        request = request.header(key.as_str(), value.as_str());
+    }
+    request
+}
+
 pub async fn download_video(
     app: &tauri::AppHandle,
     state: &DownloadState,
@@ -39,6 +57,7 @@ pub async fn download_video(
     url: String,
     name: String,
     thumbnail: Option<String>,
+    request_options: Option<StreamRequestOptions>,
 ) -> Result<DownloadedItem, String> {
     println!("Starting download: {} - {}", name, url);
     
@@ -72,9 +91,8 @@ pub async fn download_video(
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
     
     // Start download
-    let response = client
-        .get(&url)
-        .header("User-Agent", "WatchTV/1.0")
+    let request_options = request_options.unwrap_or_default();
+    let response = apply_request_options(client.get(&url), &request_options)
         .send()
         .await
         .map_err(|e| format!("Failed to start download: {}", e))?;
@@ -0,0 +1,130 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::db;
+use crate::types::{EpgNowNext, EpgProgramme};
+use super::time::shift_secs;
+
+/// A requested channel resolved to the guide channel and shift it reads from
+struct GuideChannel {
+    channel_id: String,
+    epg_channel_id: String,
+    shift: i64,
+}
+
+/// Resolve playlist channel ids through their EPG mapping, falling back to
+/// the channel's tvg-id. Ids that aren't cached channels are taken to be EPG
+/// channel ids already. Channels pinned to "no guide" are dropped.
+async fn resolve(playlist_id: &str, channel_ids: Vec<String>) -> Result<Vec<GuideChannel>, String> {
+    let settings = db::get_epg_settings(playlist_id.to_string()).await?;
+    let playlist_shift = shift_secs(settings.shift_hours);
+    
+    let mappings: HashMap<String, Option<String>> = db::get_epg_mappings_for(playlist_id.to_string(), channel_ids.clone())
+        .await?
+        .into_iter()
+        .map(|m| (m.channel_id, m.epg_channel_id))
+        .collect();
+    let channels: HashMap<String, db::EpgChannelRef> = db::get_channel_refs(playlist_id.to_string(), channel_ids.clone())
+        .await?
+        .into_iter()
+        .map(|c| (c.id.clone(), c))
+        .collect();
+    
+    Ok(channel_ids
+        .into_iter()
+        .filter_map(|id| {
+            let channel = channels.get(&id);
+            let epg_channel_id = match mappings.get(&id) {
+                Some(mapped) => mapped.clone(),
+                None => match channel {
+                    Some(c) => c.tvg_id.clone().filter(|t| !t.is_empty()),
+                    None => Some(id.clone()),
+                },
+            }?;
+            let channel_shift = channel
+                .and_then(|c| c.tvg_shift)
+                .map(|hours| shift_secs(hours as f64))
+                .unwrap_or(0);
+            Some(GuideChannel {
+                channel_id: id,
+                epg_channel_id,
+                shift: channel_shift + playlist_shift,
+            })
+        })
+        .collect())
+}
+
+/// Channels sharing a shift can be fetched with one query
+fn by_shift(channels: &[GuideChannel]) -> BTreeMap<i64, Vec<&GuideChannel>> {
+    let mut groups: BTreeMap<i64, Vec<&GuideChannel>> = BTreeMap::new();
+    for channel in channels {
+        groups.entry(channel.shift).or_default().push(channel);
+    }
+    groups
+}
+
+fn shifted(programme: &EpgProgramme, shift: i64) -> EpgProgramme {
+    EpgProgramme {
+        start: programme.start + shift,
+        stop: programme.stop + shift,
+        ..programme.clone()
+    }
+}
+
+/// Current and upcoming programme per channel, keyed by the requested id,
+/// with the channel's and playlist's shift applied.
+pub async fn now_next(
+    playlist_id: String,
+    channel_ids: Vec<String>,
+    now: i64,
+) -> Result<HashMap<String, EpgNowNext>, String> {
+    let channels = resolve(&playlist_id, channel_ids).await?;
+    let mut result = HashMap::new();
+    
+    for (shift, group) in by_shift(&channels) {
+        let epg_ids = group.iter().map(|c| c.epg_channel_id.clone()).collect();
+        // Stored times are unshifted, so look them up at the unshifted "now"
+        let guide = db::get_epg_now_next(playlist_id.clone(), epg_ids, now - shift).await?;
+        
+        for channel in group {
+            if let Some(entry) = guide.get(&channel.epg_channel_id) {
+                result.insert(
+                    channel.channel_id.clone(),
+                    EpgNowNext {
+                        now: entry.now.as_ref().map(|p| shifted(p, shift)),
+                        next: entry.next.as_ref().map(|p| shifted(p, shift)),
+                    },
+                );
+            }
+        }
+    }
+    
+    Ok(result)
+}
+
+/// Programmes overlapping `[start, end)` per channel, keyed by the requested
+/// id, with the channel's and playlist's shift applied.
+pub async fn grid(
+    playlist_id: String,
+    channel_ids: Vec<String>,
+    start: i64,
+    end: i64,
+) -> Result<HashMap<String, Vec<EpgProgramme>>, String> {
+    let channels = resolve(&playlist_id, channel_ids).await?;
+    let mut result = HashMap::new();
+    
+    for (shift, group) in by_shift(&channels) {
+        let epg_ids = group.iter().map(|c| c.epg_channel_id.clone()).collect();
+        let guide = db::get_epg_grid(playlist_id.clone(), epg_ids, start - shift, end - shift).await?;
+        
+        for channel in group {
+            if let Some(programmes) = guide.get(&channel.epg_channel_id) {
+                result.insert(
+                    channel.channel_id.clone(),
+                    programmes.iter().map(|p| shifted(p, shift)).collect(),
+                );
+            }
+        }
+    }
+    
+    Ok(result)
+}
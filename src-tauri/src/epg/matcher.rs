@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::types::EpgChannel;
+
+/// Fuzzy matches scoring below this are treated as no match
+const MIN_FUZZY_SCORE: f64 = 0.75;
+
+/// Confidence given to an exact normalized-name match
+const NAME_CONFIDENCE: f64 = 0.9;
+
+/// Fuzzy scores are scaled down so they always rank below exact matches
+const FUZZY_WEIGHT: f64 = 0.8;
+
+/// Quality and codec markers that providers append to channel names
+const QUALITY_TOKENS: &[&str] = &[
+    "hd", "fhd", "uhd", "sd", "hq", "lq", "4k", "8k", "hevc", "h264", "h265",
+    "1080p", "1080i", "720p", "576p", "50fps", "60fps", "raw",
+];
+
+/// Country and region prefixes such as `UK:` or `|DE|`
+const COUNTRY_TOKENS: &[&str] = &[
+    "uk", "gb", "us", "usa", "ca", "au", "nz", "ie", "de", "at", "ch", "fr", "be",
+    "nl", "es", "pt", "br", "it", "pl", "ro", "gr", "tr", "se", "no", "dk", "fi",
+    "ru", "ua", "in", "pk", "ar", "mx", "latam", "arab", "exyu",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpgMatch {
+    pub epg_channel_id: String,
+    pub confidence: f64,
+    /// "tvg_id", "name" or "fuzzy"
+    pub method: &'static str,
+}
+
+/// Drop a leading country prefix. The code must be bracketed (`[UK]`,
+/// `|DE|`, `(US)`) or followed by a separator (`UK:`, `US |`, `DE - `), so
+/// names that merely start with a word like "In" or "No" are kept.
+fn strip_country_prefix(name: &str) -> &str {
+    let trimmed = name.trim_start();
+    let (closer, inner) = match trimmed.chars().next() {
+        Some('[') => (Some(']'), &trimmed[1..]),
+        Some('(') => (Some(')'), &trimmed[1..]),
+        Some('|') => (Some('|'), &trimmed[1..]),
+        _ => (None, trimmed),
+    };
+    
+    let inner = inner.trim_start();
+    let end = inner.find(|c: char| !c.is_alphanumeric()).unwrap_or(inner.len());
+    if !COUNTRY_TOKENS.contains(&&inner[..end]) {
+        return name;
+    }
+    
+    let after = inner[end..].trim_start();
+    let rest = match (closer, after.chars().next()) {
+        (Some(closer), Some(c)) if c == closer => &after[1..],
+        (None, Some(':' | '|')) => &after[1..],
+        (None, Some('-')) if after[1..].starts_with(char::is_whitespace) => &after[1..],
+        _ => return name,
+    };
+    
+    if rest.trim().is_empty() {
+        name
+    } else {
+        rest
+    }
+}
+
+/// Reduce a channel name to a comparison key: lowercase, punctuation removed,
+/// quality markers and a leading country prefix dropped, words joined.
+pub fn normalize_name(name: &str) -> String {
+    let lower = name.to_lowercase();
+    
+    strip_country_prefix(&lower)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty() && !QUALITY_TOKENS.contains(t))
+        .collect()
+}
+
+/// EPG ids often look like `BBCOne.uk`; drop the country suffix before normalizing.
+fn normalize_epg_id(id: &str) -> String {
+    let base = match id.rsplit_once('.') {
+        Some((base, suffix)) if suffix.len() <= 3 && !base.is_empty() => base,
+        _ => id,
+    };
+    normalize_name(base)
+}
+
+fn bigrams(key: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = key.chars().collect();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Lookup tables over one playlist's EPG channels.
+pub struct EpgIndex {
+    ids: HashMap<String, String>,
+    names: HashMap<String, String>,
+    /// EPG channel id and bigram count of every indexed name
+    slots: Vec<(String, usize)>,
+    by_bigram: HashMap<(char, char), Vec<usize>>,
+}
+
+impl EpgIndex {
+    pub fn new(channels: &[EpgChannel]) -> Self {
+        let mut index = EpgIndex {
+            ids: HashMap::new(),
+            names: HashMap::new(),
+            slots: Vec::new(),
+            by_bigram: HashMap::new(),
+        };
+        
+        for channel in channels {
+            index.ids.entry(channel.id.to_lowercase()).or_insert_with(|| channel.id.clone());
+            
+            let mut keys: Vec<String> = channel.display_names.iter().map(|n| normalize_name(n)).collect();
+            keys.push(normalize_epg_id(&channel.id));
+            
+            for key in keys {
+                if key.is_empty() || index.names.contains_key(&key) {
+                    continue;
+                }
+                index.names.insert(key.clone(), channel.id.clone());
+                
+                let slot = index.slots.len();
+                let grams = bigrams(&key);
+                for gram in &grams {
+                    index.by_bigram.entry(*gram).or_default().push(slot);
+                }
+                index.slots.push((channel.id.clone(), grams.len()));
+            }
+        }
+        
+        index
+    }
+    
+    /// Best EPG channel for a playlist channel, trying the tvg-id first, then
+    /// the normalized name, then the closest fuzzy name.
+    pub fn find(&self, tvg_id: Option<&str>, name: &str) -> Option<EpgMatch> {
+        if let Some(id) = tvg_id.map(str::trim).filter(|id| !id.is_empty()) {
+            if let Some(epg_id) = self.ids.get(&id.to_lowercase()) {
+                return Some(EpgMatch {
+                    epg_channel_id: epg_id.clone(),
+                    confidence: 1.0,
+                    method: "tvg_id",
+                });
+            }
+        }
+        
+        let key = normalize_name(name);
+        if key.is_empty() {
+            return None;
+        }
+        if let Some(epg_id) = self.names.get(&key) {
+            return Some(EpgMatch {
+                epg_channel_id: epg_id.clone(),
+                confidence: NAME_CONFIDENCE,
+                method: "name",
+            });
+        }
+        
+        self.find_fuzzy(&key)
+    }
+    
+    fn find_fuzzy(&self, key: &str) -> Option<EpgMatch> {
+        let grams = bigrams(key);
+        if grams.is_empty() {
+            return None;
+        }
+        
+        // Sørensen–Dice over character bigrams, counted only for names that
+        // share at least one bigram with the query
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for gram in &grams {
+            if let Some(slots) = self.by_bigram.get(gram) {
+                for slot in slots {
+                    *shared.entry(*slot).or_default() += 1;
+                }
+            }
+        }
+        
+        let (slot, score) = shared
+            .into_iter()
+            .map(|(slot, count)| {
+                let total = grams.len() + self.slots[slot].1;
+                (slot, 2.0 * count as f64 / total as f64)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(&a.0)))?;
+        
+        (score >= MIN_FUZZY_SCORE).then(|| EpgMatch {
+            epg_channel_id: self.slots[slot].0.clone(),
+            confidence: score * FUZZY_WEIGHT,
+            method: "fuzzy",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(id: &str, names: &[&str]) -> EpgChannel {
+        EpgChannel {
+            id: id.to_string(),
+            display_names: names.iter().map(|n| n.to_string()).collect(),
+            icon: None,
+        }
+    }
+
+    fn index() -> EpgIndex {
+        EpgIndex::new(&[
+            channel("BBCOne.uk", &["BBC One"]),
+            channel("skysp1", &["Sky Sports Main Event"]),
+            channel("InTheNight.us", &["In The Night"]),
+        ])
+    }
+
+    #[test]
+    fn country_prefixes_need_a_separator() {
+        assert_eq!(normalize_name("UK: BBC One HD"), "bbcone");
+        assert_eq!(normalize_name("US | BBC One"), "bbcone");
+        assert_eq!(normalize_name("DE - Das Erste FHD"), "daserste");
+        assert_eq!(normalize_name("|DE| Das Erste"), "daserste");
+        assert_eq!(normalize_name("[UK] Sky Sports 1 (1080p)"), "skysports1");
+        assert_eq!(normalize_name("In The Night"), "inthenight");
+        assert_eq!(normalize_name("No Limit TV"), "nolimittv");
+        assert_eq!(normalize_name("It Crowd"), "itcrowd");
+        assert_eq!(normalize_name("Be-TV"), "betv");
+        assert_eq!(normalize_name("AR Rahman Live"), "arrahmanlive");
+        assert_eq!(normalize_name("US"), "us");
+    }
+
+    #[test]
+    fn exact_matches() {
+        let index = index();
+        
+        let found = index.find(Some("bbcone.UK"), "Anything").unwrap();
+        assert_eq!((found.epg_channel_id.as_str(), found.method), ("BBCOne.uk", "tvg_id"));
+        assert_eq!(found.confidence, 1.0);
+        
+        let found = index.find(None, "UK | BBC ONE FHD").unwrap();
+        assert_eq!((found.epg_channel_id.as_str(), found.method), ("BBCOne.uk", "name"));
+        assert_eq!(found.confidence, NAME_CONFIDENCE);
+        
+        let found = index.find(Some("unknown"), "In The Night HD").unwrap();
+        assert_eq!((found.epg_channel_id.as_str(), found.method), ("InTheNight.us", "name"));
+    }
+
+    #[test]
+    fn fuzzy_matches_rank_below_exact_ones() {
+        let index = index();
+        
+        let found = index.find(None, "Sky Sports Main Event UK").unwrap();
+        assert_eq!((found.epg_channel_id.as_str(), found.method), ("skysp1", "fuzzy"));
+        assert!(found.confidence < NAME_CONFIDENCE);
+        assert!(found.confidence >= MIN_FUZZY_SCORE * FUZZY_WEIGHT);
+        
+        assert!(index.find(None, "Completely Different").is_none());
+        assert!(index.find(None, "HD").is_none());
+    }
+}
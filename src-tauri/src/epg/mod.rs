@@ -0,0 +1,468 @@
+mod guide;
+mod matcher;
+mod time;
+mod xmltv;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use chrono_tz::Tz;
+use futures_util::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use crate::db;
+use crate::playlist;
+use crate::types::{EpgChannel, EpgMapping, EpgMatchSummary, EpgProgramme, EpgRefreshSummary};
+
+pub use guide::{grid, now_next};
+pub use time::{local_to_utc, parse_timezone};
+
+use matcher::EpgIndex;
+use xmltv::{parse_xmltv, XmltvProgramme};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Programmes handed from the parser thread to the database per batch
+const BATCH_SIZE: usize = 2000;
+
+/// Where a downloaded or local guide lives on disk
+struct GuideFile {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl Drop for GuideFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Download a guide to a temporary file so it can be parsed without holding
+/// the whole document in memory. Local paths are used in place.
+async fn fetch_guide(source: &str) -> Result<GuideFile, String> {
+    let source = source.trim();
+    
+    if !source.starts_with("http://") && !source.starts_with("https://") {
+        let path = playlist::local_path(source)?;
+        return Ok(GuideFile { path, temporary: false });
+    }
+    
+    // No total timeout, as large guides take a while; a stalled body still fails
+    let client = reqwest::Client::builder()
+        .connect_timeout(std::time::Duration::from_secs(30))
+        .read_timeout(std::time::Duration::from_secs(60))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    
+    let response = client
+        .get(source)
+        .header("User-Agent", "WatchTV/1.0")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch EPG: {}", e))?;
+    
+    if !response.status().is_success() {
+        return Err(format!("HTTP error: {}", response.status()));
+    }
+    
+    let guide = GuideFile {
+        path: std::env::temp_dir().join(format!("watchtv-epg-{}.xml", uuid::Uuid::new_v4())),
+        temporary: true,
+    };
+    let mut file = tokio::fs::File::create(&guide.path)
+        .await
+        .map_err(|e| format!("Failed to create EPG file: {}", e))?;
+    
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Failed to download EPG: {}", e))?;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to write EPG file: {}", e))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write EPG file: {}", e))?;
+    
+    Ok(guide)
+}
+
+/// `ETag` and `Last-Modified` of a guide, from a `HEAD` request or the
+/// file's modification time, so unchanged guides can be skipped.
+pub async fn guide_validators(source: &str) -> Result<(Option<String>, Option<String>), String> {
+    let source = source.trim();
+    
+    if !source.starts_with("http://") && !source.starts_with("https://") {
+        let path = playlist::local_path(source)?;
+        let modified = tokio::fs::metadata(&path)
+            .await
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("Failed to read EPG file {}: {}", path.display(), e))?;
+        let modified: chrono::DateTime<chrono::Utc> = modified.into();
+        return Ok((None, Some(modified.to_rfc2822())));
+    }
+    
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    
+    let response = client
+        .head(source)
+        .header("User-Agent", "WatchTV/1.0")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch EPG: {}", e))?;
+    
+    let header = |name: reqwest::header::HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    Ok((header(reqwest::header::ETAG), header(reqwest::header::LAST_MODIFIED)))
+}
+
+/// Open a guide file, transparently decompressing `.xml.gz` content.
+fn open_guide(path: &Path) -> Result<Box<dyn BufRead>, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open EPG file {}: {}", path.display(), e))?;
+    let mut reader = BufReader::new(file);
+    
+    let is_gzip = reader
+        .fill_buf()
+        .map_err(|e| format!("Failed to read EPG file: {}", e))?
+        .starts_with(GZIP_MAGIC);
+    
+    if is_gzip {
+        Ok(Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+fn to_programme(programme: XmltvProgramme, stop: i64) -> EpgProgramme {
+    EpgProgramme {
+        channel_id: programme.channel,
+        start: programme.start,
+        stop,
+        title: programme.title,
+        description: programme.desc,
+        categories: programme.categories,
+        icon: programme.icon,
+    }
+}
+
+/// Parse a guide on a blocking thread, sending programmes in batches.
+/// Programmes without a `stop` end where the channel's next one starts.
+fn parse_guide(path: &Path, tz: Tz, tx: mpsc::Sender<Vec<EpgProgramme>>) -> Result<Vec<EpgChannel>, String> {
+    let reader = open_guide(path)?;
+    
+    let mut channels = Vec::new();
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut open_ended: HashMap<String, XmltvProgramme> = HashMap::new();
+    let mut closed = false;
+    
+    let mut push = |programme: EpgProgramme| {
+        if closed || programme.stop <= programme.start {
+            return;
+        }
+        batch.push(programme);
+        if batch.len() >= BATCH_SIZE {
+            // The receiver is gone when storing failed; stop sending
+            closed = tx.blocking_send(std::mem::take(&mut batch)).is_err();
+        }
+    };
+    
+    parse_xmltv(
+        reader,
+        tz,
+        |channel| {
+            channels.push(EpgChannel {
+                id: channel.id,
+                display_names: channel.display_names,
+                icon: channel.icon,
+            })
+        },
+        |programme| {
+            if let Some(previous) = open_ended.remove(&programme.channel) {
+                let stop = programme.start;
+                push(to_programme(previous, stop));
+            }
+            match programme.stop {
+                Some(stop) => push(to_programme(programme, stop)),
+                None => {
+                    open_ended.insert(programme.channel.clone(), programme);
+                }
+            }
+        },
+    )?;
+    
+    // Anything left in `open_ended` has no stop and no successor, so no known end
+    if !batch.is_empty() && !closed {
+        let _ = tx.blocking_send(batch);
+    }
+    
+    Ok(channels)
+}
+
+/// Store a guide's programmes under `staging_id`, returning its channels
+async fn ingest_guide(staging_id: &str, guide: GuideFile, tz: Tz) -> Result<(Vec<EpgChannel>, usize), String> {
+    let path = guide.path.clone();
+    
+    let (tx, mut rx) = mpsc::channel::<Vec<EpgProgramme>>(4);
+    let parser = tokio::task::spawn_blocking(move || parse_guide(&path, tz, tx));
+    
+    let mut programmes = 0;
+    while let Some(batch) = rx.recv().await {
+        programmes += batch.len();
+        db::cache_programmes(staging_id.to_string(), batch).await?;
+    }
+    
+    let channels = parser
+        .await
+        .map_err(|e| format!("EPG parser failed: {}", e))??;
+    
+    // Removes the temporary download
+    drop(guide);
+    Ok((channels, programmes))
+}
+
+/// Replace a playlist's guide with the given XMLTV sources. When no URLs are
+/// given, the `url-tvg` of the playlist at `playlist_source` is used.
+pub async fn refresh_epg(
+    playlist_id: String,
+    urls: Vec<String>,
+    playlist_source: Option<String>,
+) -> Result<EpgRefreshSummary, String> {
+    let mut sources: Vec<String> = urls
+        .into_iter()
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty())
+        .collect();
+    
+    if sources.is_empty() {
+        if let Some(source) = playlist_source {
+            sources = playlist::read_playlist_header(&source).await?.epg_urls;
+        }
+    }
+    
+    if sources.is_empty() {
+        return Err("No EPG source available for this playlist".to_string());
+    }
+    
+    let tz = guide_timezone(&playlist_id, None).await?;
+    refresh_xmltv(playlist_id, sources, tz).await
+}
+
+/// Zone for guide times that carry no offset: the playlist's EPG setting,
+/// then `fallback` (e.g. the Xtream server zone), then UTC.
+pub async fn guide_timezone(playlist_id: &str, fallback: Option<&str>) -> Result<Tz, String> {
+    let settings = db::get_epg_settings(playlist_id.to_string()).await?;
+    Ok(settings
+        .timezone
+        .as_deref()
+        .and_then(parse_timezone)
+        .or_else(|| fallback.and_then(parse_timezone))
+        .unwrap_or(chrono_tz::UTC))
+}
+
+/// Replace a playlist's guide with the given XMLTV sources, reading times
+/// without an offset as local to `tz`.
+pub async fn refresh_xmltv(
+    playlist_id: String,
+    sources: Vec<String>,
+    tz: Tz,
+) -> Result<EpgRefreshSummary, String> {
+    println!("Rust: Refreshing EPG for {} from {} source(s)", playlist_id, sources.len());
+    
+    // Download everything first so a dead source leaves the old guide intact
+    let mut guides = Vec::new();
+    let mut last_error = None;
+    for source in sources {
+        match fetch_guide(&source).await {
+            Ok(guide) => guides.push((source, guide)),
+            // One broken mirror shouldn't discard the others
+            Err(e) => {
+                eprintln!("Failed to load EPG from {}: {}", source, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    
+    if guides.is_empty() {
+        return Err(last_error.unwrap_or_else(|| "Failed to load EPG".to_string()));
+    }
+    
+    let mut summary = EpgRefreshSummary {
+        playlist_id: playlist_id.clone(),
+        sources: Vec::new(),
+        channels: 0,
+        programmes: 0,
+    };
+    let mut channels = Vec::new();
+    let mut staged = Vec::new();
+    
+    // Parse each source into its own staging area first, so a broken guide
+    // leaves the old one intact and its partial programmes are never used
+    for (index, (source, guide)) in guides.into_iter().enumerate() {
+        let staging_id = db::epg_staging_id(&playlist_id, index);
+        db::clear_epg(staging_id.clone()).await?;
+        match ingest_guide(&staging_id, guide, tz).await {
+            Ok((guide_channels, programmes)) => {
+                summary.channels += guide_channels.len();
+                summary.programmes += programmes;
+                summary.sources.push(source);
+                channels.extend(guide_channels);
+                staged.push(staging_id);
+            }
+            Err(e) => {
+                eprintln!("Failed to parse EPG from {}: {}", source, e);
+                db::clear_epg(staging_id).await?;
+                last_error = Some(e);
+            }
+        }
+    }
+    
+    if summary.sources.is_empty() {
+        return Err(last_error.unwrap_or_else(|| "Failed to load EPG".to_string()));
+    }
+    
+    if let Err(e) = db::replace_epg(playlist_id.clone(), staged.clone(), channels).await {
+        for staging_id in staged {
+            db::clear_epg(staging_id).await?;
+        }
+        return Err(e);
+    }
+    
+    println!(
+        "Rust: Cached {} EPG channels and {} programmes",
+        summary.channels, summary.programmes
+    );
+    
+    // EPG ids may have changed, so re-link the playlist's channels
+    if let Err(e) = match_channels(playlist_id).await {
+        eprintln!("Failed to match EPG channels: {}", e);
+    }
+    
+    Ok(summary)
+}
+
+/// Replace a playlist's guide with programmes gathered outside of XMLTV,
+/// such as per-stream provider APIs.
+pub async fn replace_guide(
+    playlist_id: String,
+    source: String,
+    channels: Vec<EpgChannel>,
+    programmes: Vec<EpgProgramme>,
+) -> Result<EpgRefreshSummary, String> {
+    let programmes: Vec<EpgProgramme> = programmes
+        .into_iter()
+        .filter(|p| p.stop > p.start)
+        .collect();
+    
+    let summary = EpgRefreshSummary {
+        playlist_id: playlist_id.clone(),
+        sources: vec![source],
+        channels: channels.len(),
+        programmes: programmes.len(),
+    };
+    
+    let staging_id = db::epg_staging_id(&playlist_id, 0);
+    db::clear_epg(staging_id.clone()).await?;
+    let stored = match db::cache_programmes(staging_id.clone(), programmes).await {
+        Ok(()) => db::replace_epg(playlist_id.clone(), vec![staging_id.clone()], channels).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = stored {
+        db::clear_epg(staging_id).await?;
+        return Err(e);
+    }
+    
+    if let Err(e) = match_channels(playlist_id).await {
+        eprintln!("Failed to match EPG channels: {}", e);
+    }
+    
+    Ok(summary)
+}
+
+/// Link every live channel of a playlist to the guide, replacing earlier
+/// automatic matches. Channels with a manual override are left untouched.
+pub async fn match_channels(playlist_id: String) -> Result<EpgMatchSummary, String> {
+    let index = EpgIndex::new(&db::get_epg_channels(playlist_id.clone()).await?);
+    let manual: HashSet<String> = db::get_epg_mappings(playlist_id.clone())
+        .await?
+        .into_iter()
+        .filter(|m| m.manual)
+        .map(|m| m.channel_id)
+        .collect();
+    
+    let now = chrono::Utc::now().timestamp();
+    let mut summary = EpgMatchSummary {
+        playlist_id: playlist_id.clone(),
+        ..Default::default()
+    };
+    let mut mappings = Vec::new();
+    
+    for channel in db::get_live_channel_refs(playlist_id.clone()).await? {
+        summary.channels += 1;
+        if manual.contains(&channel.id) {
+            summary.manual += 1;
+            continue;
+        }
+        
+        let Some(found) = index.find(channel.tvg_id.as_deref(), &channel.name) else {
+            summary.unmatched += 1;
+            continue;
+        };
+        match found.method {
+            "tvg_id" => summary.by_tvg_id += 1,
+            "name" => summary.by_name += 1,
+            _ => summary.fuzzy += 1,
+        }
+        mappings.push(EpgMapping {
+            playlist_id: playlist_id.clone(),
+            channel_id: channel.id,
+            epg_channel_id: Some(found.epg_channel_id),
+            confidence: found.confidence,
+            method: found.method.to_string(),
+            manual: false,
+            updated_at: now,
+        });
+    }
+    
+    db::replace_auto_epg_mappings(playlist_id, mappings).await?;
+    
+    println!(
+        "Rust: Matched EPG for {} of {} channels ({} manual)",
+        summary.channels - summary.unmatched - summary.manual,
+        summary.channels,
+        summary.manual
+    );
+    Ok(summary)
+}
+
+/// Pin a channel to an EPG channel, or to no guide at all with `None`.
+/// Overrides are kept across playlist refreshes and re-matching.
+pub async fn set_manual_mapping(
+    playlist_id: String,
+    channel_id: String,
+    epg_channel_id: Option<String>,
+) -> Result<EpgMapping, String> {
+    let mapping = EpgMapping {
+        playlist_id,
+        channel_id,
+        epg_channel_id,
+        confidence: 1.0,
+        method: "manual".to_string(),
+        manual: true,
+        updated_at: chrono::Utc::now().timestamp(),
+    };
+    db::save_epg_mapping(mapping.clone()).await?;
+    Ok(mapping)
+}
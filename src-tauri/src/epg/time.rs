@@ -0,0 +1,92 @@
+use chrono::{Duration, LocalResult, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+/// Resolve an IANA zone name such as `Europe/Berlin`; anything unknown is `None`.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.trim().parse::<Tz>().ok()
+}
+
+/// Interpret a wall-clock time in `tz` as UTC seconds.
+///
+/// Times repeated when clocks go back resolve to their first occurrence, and
+/// times skipped when clocks go forward are read as if the clock had not yet
+/// changed, i.e. moved past the gap.
+pub fn local_to_utc(naive: &NaiveDateTime, tz: Tz) -> i64 {
+    match tz.from_local_datetime(naive) {
+        LocalResult::Single(time) => time.timestamp(),
+        LocalResult::Ambiguous(earliest, _) => earliest.timestamp(),
+        LocalResult::None => {
+            // DST gaps are at most an hour in every zone that has them
+            let after = *naive + Duration::hours(1);
+            match tz.from_local_datetime(&after) {
+                LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.timestamp(),
+                LocalResult::None => naive.and_utc().timestamp(),
+            }
+        }
+    }
+}
+
+/// Seconds for a `tvg-shift` style offset in (possibly fractional) hours
+pub fn shift_secs(hours: f64) -> i64 {
+    (hours * 3600.0).round() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epg::xmltv::parse_xmltv_time;
+
+    fn naive(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn utc(value: &str) -> i64 {
+        naive(value).and_utc().timestamp()
+    }
+
+    #[test]
+    fn local_times_around_spring_forward() {
+        let london = parse_timezone("Europe/London").unwrap();
+        // Clocks go from 01:00 GMT to 02:00 BST on 2024-03-31
+        assert_eq!(local_to_utc(&naive("2024-03-31 00:30:00"), london), utc("2024-03-31 00:30:00"));
+        assert_eq!(local_to_utc(&naive("2024-03-31 02:30:00"), london), utc("2024-03-31 01:30:00"));
+        // 01:30 never happens locally; it is read as 02:30 BST
+        assert_eq!(local_to_utc(&naive("2024-03-31 01:30:00"), london), utc("2024-03-31 01:30:00"));
+    }
+
+    #[test]
+    fn local_times_around_fall_back() {
+        let berlin = parse_timezone("Europe/Berlin").unwrap();
+        // Clocks go from 03:00 CEST back to 02:00 CET on 2024-10-27
+        assert_eq!(local_to_utc(&naive("2024-10-27 01:30:00"), berlin), utc("2024-10-26 23:30:00"));
+        // 02:30 happens twice; the first (CEST) occurrence wins
+        assert_eq!(local_to_utc(&naive("2024-10-27 02:30:00"), berlin), utc("2024-10-27 00:30:00"));
+        assert_eq!(local_to_utc(&naive("2024-10-27 03:30:00"), berlin), utc("2024-10-27 02:30:00"));
+    }
+
+    #[test]
+    fn programme_spanning_a_transition_keeps_its_real_length() {
+        let new_york = parse_timezone("America/New_York").unwrap();
+        // 01:00–04:00 local on 2024-03-10 is only two real hours
+        let start = local_to_utc(&naive("2024-03-10 01:00:00"), new_york);
+        let stop = local_to_utc(&naive("2024-03-10 04:00:00"), new_york);
+        assert_eq!(stop - start, 2 * 3600);
+    }
+
+    #[test]
+    fn xmltv_offsets_override_the_default_zone() {
+        let london = parse_timezone("Europe/London").unwrap();
+        let summer = parse_xmltv_time("20240701120000 +0200", london).unwrap();
+        assert_eq!(summer, utc("2024-07-01 10:00:00"));
+        // Without an offset the guide's zone applies, including its DST rules
+        assert_eq!(parse_xmltv_time("20240701120000", london), Some(utc("2024-07-01 11:00:00")));
+        assert_eq!(parse_xmltv_time("20240101120000", london), Some(utc("2024-01-01 12:00:00")));
+    }
+
+    #[test]
+    fn shifts_are_whole_seconds() {
+        assert_eq!(shift_secs(1.0), 3600);
+        assert_eq!(shift_secs(-1.5), -5400);
+        assert_eq!(shift_secs(0.25), 900);
+    }
+}
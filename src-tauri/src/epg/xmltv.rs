@@ -0,0 +1,228 @@
+use std::io::BufRead;
+
+use chrono::{FixedOffset, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use super::time::local_to_utc;
+
+#[derive(Debug, Clone, Default)]
+pub struct XmltvChannel {
+    pub id: String,
+    pub display_names: Vec<String>,
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XmltvProgramme {
+    pub channel: String,
+    /// Unix timestamps in UTC
+    pub start: i64,
+    pub stop: Option<i64>,
+    pub title: String,
+    pub desc: Option<String>,
+    pub categories: Vec<String>,
+    pub icon: Option<String>,
+}
+
+/// Parse an XMLTV timestamp such as `20240301183000 +0200` into UTC seconds.
+/// Missing seconds/minutes are tolerated; without an offset the time is
+/// local to `default_tz`.
+pub fn parse_xmltv_time(value: &str, default_tz: Tz) -> Option<i64> {
+    let value = value.trim();
+    let (datetime, offset) = match value.split_once(char::is_whitespace) {
+        Some((datetime, offset)) => (datetime, Some(offset.trim())),
+        None => (value, None),
+    };
+
+    // Pad YYYYMMDDhhmm / YYYYMMDDhh to a full timestamp
+    let digits: String = datetime.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 8 {
+        return None;
+    }
+    let mut padded = digits.clone();
+    while padded.len() < 14 {
+        padded.push('0');
+    }
+    let naive = NaiveDateTime::parse_from_str(&padded[..14], "%Y%m%d%H%M%S").ok()?;
+
+    // The offset may also be glued onto the timestamp (`20240301183000+0200`)
+    let offset = offset.or_else(|| {
+        let rest = &datetime[digits.len()..];
+        (!rest.is_empty()).then_some(rest)
+    });
+
+    let seconds = match offset.and_then(parse_offset) {
+        Some(offset) => offset.from_local_datetime(&naive).single()?.timestamp(),
+        None => local_to_utc(&naive, default_tz),
+    };
+    Some(seconds)
+}
+
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("z") || value.eq_ignore_ascii_case("utc") || value.eq_ignore_ascii_case("gmt") {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, digits) = match value.chars().next()? {
+        '+' => (1, &value[1..]),
+        '-' => (-1, &value[1..]),
+        _ => (1, value),
+    };
+    let digits: String = digits.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..4].parse().ok()?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+enum Field {
+    None,
+    DisplayName,
+    Title,
+    Desc,
+    Category,
+}
+
+/// Stream-parse an XMLTV document, handing every `<channel>` and `<programme>`
+/// to the callbacks as soon as its closing tag is read.
+pub fn parse_xmltv<R, C, P>(reader: R, default_tz: Tz, mut on_channel: C, mut on_programme: P) -> Result<(), String>
+where
+    R: BufRead,
+    C: FnMut(XmltvChannel),
+    P: FnMut(XmltvProgramme),
+{
+    let mut reader = Reader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut channel: Option<XmltvChannel> = None;
+    let mut programme: Option<XmltvProgramme> = None;
+    let mut field = Field::None;
+    let mut text = String::new();
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| format!("Invalid XMLTV at byte {}: {}", reader.buffer_position(), e))?;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                match e.local_name().as_ref() {
+                    b"channel" if !is_empty => {
+                        channel = attribute(e, "id").map(|id| XmltvChannel {
+                            id,
+                            ..Default::default()
+                        });
+                    }
+                    b"programme" if !is_empty => {
+                        let start = attribute(e, "start").and_then(|v| parse_xmltv_time(&v, default_tz));
+                        let id = attribute(e, "channel");
+                        programme = match (start, id) {
+                            (Some(start), Some(channel)) => Some(XmltvProgramme {
+                                channel,
+                                start,
+                                stop: attribute(e, "stop").and_then(|v| parse_xmltv_time(&v, default_tz)),
+                                ..Default::default()
+                            }),
+                            _ => None,
+                        };
+                    }
+                    b"icon" => {
+                        let src = attribute(e, "src");
+                        if let Some(p) = programme.as_mut() {
+                            p.icon = p.icon.take().or(src);
+                        } else if let Some(c) = channel.as_mut() {
+                            c.icon = c.icon.take().or(src);
+                        }
+                    }
+                    b"display-name" if !is_empty => field = Field::DisplayName,
+                    b"title" if !is_empty => field = Field::Title,
+                    b"desc" if !is_empty => field = Field::Desc,
+                    b"category" if !is_empty => field = Field::Category,
+                    _ => {}
+                }
+                if !matches!(field, Field::None) {
+                    text.clear();
+                }
+            }
+            Event::Text(e) if !matches!(field, Field::None) => {
+                if let Ok(value) = e.unescape() {
+                    text.push_str(&value);
+                }
+            }
+            Event::CData(e) if !matches!(field, Field::None) => {
+                text.push_str(&String::from_utf8_lossy(&e));
+            }
+            Event::End(e) => {
+                let value = text.trim().to_string();
+                match e.local_name().as_ref() {
+                    b"display-name" => {
+                        if let Some(c) = channel.as_mut() {
+                            if !value.is_empty() {
+                                c.display_names.push(value);
+                            }
+                        }
+                        field = Field::None;
+                    }
+                    // Only the first title/desc is kept when several languages are present
+                    b"title" => {
+                        if let Some(p) = programme.as_mut() {
+                            if p.title.is_empty() {
+                                p.title = value;
+                            }
+                        }
+                        field = Field::None;
+                    }
+                    b"desc" => {
+                        if let Some(p) = programme.as_mut() {
+                            if p.desc.is_none() && !value.is_empty() {
+                                p.desc = Some(value);
+                            }
+                        }
+                        field = Field::None;
+                    }
+                    b"category" => {
+                        if let Some(p) = programme.as_mut() {
+                            if !value.is_empty() && !p.categories.contains(&value) {
+                                p.categories.push(value);
+                            }
+                        }
+                        field = Field::None;
+                    }
+                    b"channel" => {
+                        if let Some(c) = channel.take() {
+                            on_channel(c);
+                        }
+                    }
+                    b"programme" => {
+                        if let Some(p) = programme.take() {
+                            on_programme(p);
+                        }
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(())
+}
@@ -1,21 +1,52 @@
 mod db;
 mod download;
+mod epg;
 mod playlist;
+mod scheduler;
 mod transcode;
 mod types;
+mod xtream;
 
 use std::collections::HashMap;
+use tauri::Emitter;
 
 pub use types::*;
 pub use db::{init_db, get_db};
+pub use playlist::{parse_m3u_content, parse_m3u_lenient, M3uParser};
 pub use download::DownloadState;
 pub use transcode::TranscodeState;
+pub use xtream::XtreamClient;
 
 // ==================== Tauri Commands ====================
 
 #[tauri::command]
-async fn fetch_and_parse_m3u(url: String) -> Result<ParsedPlaylist, String> {
-    playlist::fetch_and_parse_m3u(&url).await
+async fn fetch_and_parse_m3u(app: tauri::AppHandle, url: String) -> Result<ParsedPlaylist, String> {
+    playlist::fetch_and_parse_m3u(&app, &url).await
+}
+
+#[tauri::command]
+async fn load_playlist_source(source: String) -> Result<ParsedPlaylist, String> {
+    playlist::load_playlist_source(&source).await
+}
+
+#[tauri::command]
+async fn load_playlist_with_report(source: String) -> Result<ParsedPlaylistReport, String> {
+    playlist::load_playlist_with_report(&source).await
+}
+
+#[tauri::command]
+fn write_m3u(playlist: ParsedPlaylist) -> String {
+    playlist::write_m3u(&playlist)
+}
+
+#[tauri::command]
+async fn export_playlist_m3u(playlist: ParsedPlaylist, path: String) -> Result<(), String> {
+    playlist::export_m3u(&path, playlist::write_m3u(&playlist)).await
+}
+
+#[tauri::command]
+async fn export_channels_m3u(channels: Vec<CachedChannel>, path: String) -> Result<(), String> {
+    playlist::export_m3u(&path, playlist::write_channels_m3u(&channels)).await
 }
 
 #[tauri::command]
@@ -26,8 +57,9 @@ async fn download_video(
     url: String,
     name: String,
     thumbnail: Option<String>,
+    request_options: Option<StreamRequestOptions>,
 ) -> Result<DownloadedItem, String> {
-    download::download_video(&app, &state, id, url, name, thumbnail).await
+    download::download_video(&app, &state, id, url, name, thumbnail, request_options).await
 }
 
 #[tauri::command]
@@ -52,8 +84,9 @@ fn get_downloads_path() -> Result<String, String> {
 async fn start_transcode(
     state: tauri::State<'_, TranscodeState>,
     source_path: String,
+    request_options: Option<StreamRequestOptions>,
 ) -> Result<String, String> {
-    transcode::start_transcode(&state, &source_path).await
+    transcode::start_transcode(&state, &source_path, request_options).await
 }
 
 #[tauri::command]
@@ -70,19 +103,222 @@ fn needs_transcoding(url: String) -> bool {
 
 #[tauri::command]
 async fn cache_playlist_data(
+    app: tauri::AppHandle,
     playlist_id: String,
     categories: Vec<CachedCategory>,
     channels: Vec<CachedChannel>,
+) -> Result<CacheSummary, String> {
+    let _guard = scheduler::lock_refresh(&playlist_id).await;
+    db::cache_playlist_data(playlist_id, categories, channels, |progress| {
+        let _ = app.emit("cache-progress", &progress);
+    })
+    .await
+}
+
+#[tauri::command]
+async fn fetch_and_cache_m3u_playlist(
+    app: tauri::AppHandle,
+    playlist_id: String,
+    url: String,
+) -> Result<CacheSummary, String> {
+    let _guard = scheduler::lock_refresh(&playlist_id).await;
+    playlist::fetch_and_cache_m3u(&app, playlist_id, &url).await
+}
+
+#[tauri::command]
+async fn fetch_and_cache_xtream_playlist(
+    app: tauri::AppHandle,
+    playlist_id: String,
+    credentials: XtreamCredentials,
+) -> Result<CacheSummary, String> {
+    let _guard = scheduler::lock_refresh(&playlist_id).await;
+    xtream::fetch_and_cache_playlist(&app, playlist_id, credentials).await
+}
+
+#[tauri::command]
+async fn refresh_xtream_account(
+    app: tauri::AppHandle,
+    playlist_id: String,
+    credentials: XtreamCredentials,
+    warn_days: Option<u32>,
+) -> Result<XtreamAccount, String> {
+    xtream::refresh_account(&app, playlist_id, credentials, warn_days).await
+}
+
+#[tauri::command]
+async fn get_xtream_account(
+    playlist_id: String,
+) -> Result<Option<XtreamAccount>, String> {
+    db::get_xtream_account(playlist_id).await
+}
+
+#[tauri::command]
+async fn fetch_series_info(
+    playlist_id: String,
+    credentials: XtreamCredentials,
+    series_id: String,
+) -> Result<SeriesDetails, String> {
+    xtream::fetch_and_cache_series(playlist_id, credentials, series_id).await
+}
+
+#[tauri::command]
+async fn get_cached_series(
+    playlist_id: String,
+    series_id: String,
+) -> Result<Option<Series>, String> {
+    db::get_cached_series(playlist_id, series_id).await
+}
+
+#[tauri::command]
+async fn get_cached_seasons(
+    playlist_id: String,
+    series_id: String,
+) -> Result<Vec<Season>, String> {
+    db::get_cached_seasons(playlist_id, series_id).await
+}
+
+#[tauri::command]
+async fn get_cached_episodes(
+    playlist_id: String,
+    series_id: String,
+    season_number: Option<i64>,
+) -> Result<Vec<Episode>, String> {
+    db::get_cached_episodes(playlist_id, series_id, season_number).await
+}
+
+#[tauri::command]
+async fn get_vod_metadata(
+    playlist_id: String,
+    content_type: String,
+    channel_id: String,
+    credentials: Option<XtreamCredentials>,
+    max_age_secs: Option<i64>,
+) -> Result<Option<VodMetadata>, String> {
+    xtream::get_vod_metadata(playlist_id, content_type, channel_id, credentials, max_age_secs).await
+}
+
+#[tauri::command]
+async fn refresh_epg(
+    playlist_id: String,
+    urls: Option<Vec<String>>,
+    playlist_source: Option<String>,
+) -> Result<EpgRefreshSummary, String> {
+    let _guard = scheduler::lock_refresh(&playlist_id).await;
+    epg::refresh_epg(playlist_id, urls.unwrap_or_default(), playlist_source).await
+}
+
+#[tauri::command]
+async fn refresh_xtream_epg(
+    playlist_id: String,
+    credentials: XtreamCredentials,
+) -> Result<EpgRefreshSummary, String> {
+    let _guard = scheduler::lock_refresh(&playlist_id).await;
+    xtream::refresh_epg(playlist_id, credentials).await
+}
+
+#[tauri::command]
+async fn get_epg_now_next(
+    playlist_id: String,
+    channel_ids: Vec<String>,
+) -> Result<HashMap<String, EpgNowNext>, String> {
+    epg::now_next(playlist_id, channel_ids, chrono::Utc::now().timestamp()).await
+}
+
+#[tauri::command]
+async fn get_epg_grid(
+    playlist_id: String,
+    channel_ids: Vec<String>,
+    start: i64,
+    end: i64,
+) -> Result<HashMap<String, Vec<EpgProgramme>>, String> {
+    epg::grid(playlist_id, channel_ids, start, end).await
+}
+
+#[tauri::command]
+async fn get_epg_settings(
+    playlist_id: String,
+) -> Result<EpgSettings, String> {
+    db::get_epg_settings(playlist_id).await
+}
+
+#[tauri::command]
+async fn save_epg_settings(
+    settings: EpgSettings,
+) -> Result<(), String> {
+    if let Some(tz) = settings.timezone.as_deref().filter(|tz| !tz.trim().is_empty()) {
+        epg::parse_timezone(tz).ok_or_else(|| format!("Unknown timezone: {}", tz))?;
+    }
+    db::save_epg_settings(settings).await
+}
+
+#[tauri::command]
+async fn match_epg_channels(
+    playlist_id: String,
+) -> Result<EpgMatchSummary, String> {
+    epg::match_channels(playlist_id).await
+}
+
+#[tauri::command]
+async fn get_epg_mappings(
+    playlist_id: String,
+) -> Result<Vec<EpgMapping>, String> {
+    db::get_epg_mappings(playlist_id).await
+}
+
+#[tauri::command]
+async fn set_epg_mapping(
+    playlist_id: String,
+    channel_id: String,
+    epg_channel_id: Option<String>,
+) -> Result<EpgMapping, String> {
+    epg::set_manual_mapping(playlist_id, channel_id, epg_channel_id).await
+}
+
+#[tauri::command]
+async fn reset_epg_mapping(
+    playlist_id: String,
+    channel_id: String,
+) -> Result<(), String> {
+    db::delete_epg_mapping(playlist_id, channel_id).await
+}
+
+#[tauri::command]
+async fn schedule_playlist_refresh(
+    playlist: ScheduledPlaylist,
+) -> Result<ScheduledPlaylist, String> {
+    scheduler::schedule(playlist).await
+}
+
+#[tauri::command]
+async fn unschedule_playlist_refresh(
+    playlist_id: String,
 ) -> Result<(), String> {
-    db::cache_playlist_data(playlist_id, categories, channels).await
+    scheduler::unschedule(playlist_id).await
+}
+
+#[tauri::command]
+async fn get_scheduled_playlists() -> Result<Vec<ScheduledPlaylist>, String> {
+    db::get_scheduled_playlists().await
+}
+
+#[tauri::command]
+async fn refresh_playlist_now(
+    app: tauri::AppHandle,
+    playlist_id: String,
+) -> Result<PlaylistRefreshed, String> {
+    let playlist = db::get_scheduled_playlist(playlist_id.clone())
+        .await?
+        .ok_or_else(|| format!("Playlist {} is not scheduled", playlist_id))?;
+    scheduler::refresh_playlist(&app, playlist).await
 }
 
 #[tauri::command]
 async fn get_cached_categories(
     playlist_id: String,
     content_type: String,
+    include_hidden: Option<bool>,
 ) -> Result<Vec<CachedCategory>, String> {
-    db::get_cached_categories(playlist_id, content_type).await
+    db::get_cached_categories(playlist_id, content_type, include_hidden.unwrap_or(false)).await
 }
 
 #[tauri::command]
@@ -94,6 +330,34 @@ async fn get_cached_channels(
     db::get_cached_channels(playlist_id, category_id, content_type).await
 }
 
+#[tauri::command]
+async fn get_channel_page(
+    playlist_id: String,
+    category_id: Option<String>,
+    content_type: String,
+    sort: Option<ChannelSort>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<ChannelPage, String> {
+    db::get_channel_page(
+        playlist_id,
+        category_id,
+        content_type,
+        sort.unwrap_or_default(),
+        limit.unwrap_or(100),
+        cursor,
+    ).await
+}
+
+#[tauri::command]
+async fn count_cached_channels(
+    playlist_id: String,
+    category_id: Option<String>,
+    content_type: String,
+) -> Result<usize, String> {
+    db::count_cached_channels(playlist_id, category_id, content_type).await
+}
+
 #[tauri::command]
 async fn is_playlist_cached(
     playlist_id: String,
@@ -114,7 +378,7 @@ async fn search_cached_channels(
     query: String,
     content_type: Option<String>,
     limit: Option<i32>,
-) -> Result<Vec<CachedChannel>, String> {
+) -> Result<Vec<ChannelSearchResult>, String> {
     db::search_cached_channels(
         playlist_id,
         query,
@@ -123,6 +387,15 @@ async fn search_cached_channels(
     ).await
 }
 
+#[tauri::command]
+async fn search_all_playlists(
+    query: String,
+    content_type: Option<String>,
+    limit: Option<i32>,
+) -> Result<GlobalSearchResults, String> {
+    db::search_all_playlists(query, content_type, limit.unwrap_or(50)).await
+}
+
 #[tauri::command]
 async fn get_content_availability(
     playlist_id: String,
@@ -130,6 +403,143 @@ async fn get_content_availability(
     db::get_content_availability(playlist_id).await
 }
 
+#[tauri::command]
+async fn add_favorite(
+    playlist_id: String,
+    content_type: String,
+    channel_id: String,
+) -> Result<Favorite, String> {
+    db::add_favorite(playlist_id, content_type, channel_id).await
+}
+
+#[tauri::command]
+async fn remove_favorite(
+    playlist_id: String,
+    content_type: String,
+    channel_id: String,
+) -> Result<(), String> {
+    db::remove_favorite(playlist_id, content_type, channel_id).await
+}
+
+#[tauri::command]
+async fn get_favorites(
+    playlist_id: String,
+    content_type: Option<String>,
+) -> Result<Vec<Favorite>, String> {
+    db::get_favorites(playlist_id, content_type).await
+}
+
+#[tauri::command]
+async fn reorder_favorites(
+    playlist_id: String,
+    content_type: String,
+    channel_ids: Vec<String>,
+) -> Result<Vec<Favorite>, String> {
+    db::reorder_favorites(playlist_id, content_type, channel_ids).await
+}
+
+#[tauri::command]
+async fn get_channel_groups() -> Result<Vec<ChannelGroup>, String> {
+    db::get_channel_groups().await
+}
+
+#[tauri::command]
+async fn create_channel_group(
+    name: String,
+) -> Result<ChannelGroup, String> {
+    db::create_channel_group(name).await
+}
+
+#[tauri::command]
+async fn rename_channel_group(
+    group_id: String,
+    name: String,
+) -> Result<ChannelGroup, String> {
+    db::rename_channel_group(group_id, name).await
+}
+
+#[tauri::command]
+async fn delete_channel_group(
+    group_id: String,
+) -> Result<(), String> {
+    db::delete_channel_group(group_id).await
+}
+
+#[tauri::command]
+async fn reorder_channel_groups(
+    group_ids: Vec<String>,
+) -> Result<Vec<ChannelGroup>, String> {
+    db::reorder_channel_groups(group_ids).await
+}
+
+#[tauri::command]
+async fn add_to_channel_group(
+    group_id: String,
+    channel: ChannelSource,
+) -> Result<ChannelGroup, String> {
+    db::add_to_channel_group(group_id, channel).await
+}
+
+#[tauri::command]
+async fn remove_from_channel_group(
+    group_id: String,
+    channel: ChannelSource,
+) -> Result<ChannelGroup, String> {
+    db::remove_from_channel_group(group_id, channel).await
+}
+
+#[tauri::command]
+async fn set_channel_group_channels(
+    group_id: String,
+    channels: Vec<ChannelSource>,
+) -> Result<ChannelGroup, String> {
+    db::set_channel_group_channels(group_id, channels).await
+}
+
+#[tauri::command]
+async fn get_channel_group_channels(
+    group_id: String,
+) -> Result<Vec<GroupChannel>, String> {
+    db::get_channel_group_channels(group_id).await
+}
+
+#[tauri::command]
+async fn get_category_overrides(
+    playlist_id: String,
+    content_type: String,
+) -> Result<Vec<CategoryOverride>, String> {
+    db::get_category_overrides(playlist_id, content_type).await
+}
+
+#[tauri::command]
+async fn rename_category(
+    playlist_id: String,
+    content_type: String,
+    category_id: String,
+    name: Option<String>,
+) -> Result<(), String> {
+    db::rename_category(playlist_id, content_type, category_id, name).await
+}
+
+#[tauri::command]
+async fn set_category_hidden(
+    playlist_id: String,
+    content_type: String,
+    category_id: String,
+    hidden: bool,
+) -> Result<(), String> {
+    db::set_category_hidden(playlist_id, content_type, category_id, hidden).await
+}
+
+#[tauri::command]
+async fn reorder_categories(
+    playlist_id: String,
+    content_type: String,
+    category_ids: Vec<String>,
+) -> Result<(), String> {
+    db::reorder_categories(playlist_id, content_type, category_ids).await
+}
+
 #[tauri::command]
 async fn save_last_viewed_state(
     playlist_id: String,
@@ -165,18 +575,26 @@ pub fn run() {
         .manage(TranscodeState::default())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_http::init())
-        .setup(|_app| {
-            // Initialize database on startup
-            tauri::async_runtime::spawn(async {
+        .setup(|app| {
+            // Initialize database on startup, then start background refreshes
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
                 if let Err(e) = init_db().await {
                     eprintln!("Failed to initialize database: {}", e);
+                    return;
                 }
+                scheduler::start(handle);
             });
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             greet,
             fetch_and_parse_m3u,
+            load_playlist_source,
+            load_playlist_with_report,
+            write_m3u,
+            export_playlist_m3u,
+            export_channels_m3u,
             // Download commands
             download_video,
             cancel_download,
@@ -188,12 +606,59 @@ pub fn run() {
             needs_transcoding,
             // Cache commands
             cache_playlist_data,
+            fetch_and_cache_m3u_playlist,
+            fetch_and_cache_xtream_playlist,
+            refresh_xtream_account,
+            get_xtream_account,
+            fetch_series_info,
+            get_cached_series,
+            get_cached_seasons,
+            get_cached_episodes,
+            get_vod_metadata,
             get_cached_categories,
             get_cached_channels,
+            get_channel_page,
+            count_cached_channels,
             is_playlist_cached,
             clear_playlist_cache,
             search_cached_channels,
+            search_all_playlists,
             get_content_availability,
+            // EPG commands
+            refresh_epg,
+            refresh_xtream_epg,
+            get_epg_now_next,
+            get_epg_grid,
+            match_epg_channels,
+            get_epg_mappings,
+            set_epg_mapping,
+            reset_epg_mapping,
+            get_epg_settings,
+            save_epg_settings,
+            // Scheduler commands
+            schedule_playlist_refresh,
+            unschedule_playlist_refresh,
+            get_scheduled_playlists,
+            refresh_playlist_now,
+            // Favorite commands
+            add_favorite,
+            remove_favorite,
+            get_favorites,
+            reorder_favorites,
+            // Group commands
+            get_channel_groups,
+            create_channel_group,
+            rename_channel_group,
+            delete_channel_group,
+            reorder_channel_groups,
+            add_to_channel_group,
+            remove_from_channel_group,
+            set_channel_group_channels,
+            get_channel_group_channels,
+            get_category_overrides,
+            rename_category,
+            set_category_hidden,
+            reorder_categories,
             // State commands
             save_last_viewed_state,
             get_last_viewed_state
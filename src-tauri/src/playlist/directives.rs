@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use crate::types::StreamRequestOptions;
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn set_header(options: &mut StreamRequestOptions, key: &str, value: &str) {
+    match key.to_lowercase().as_str() {
+        "user-agent" => options.user_agent = Some(value.to_string()),
+        "referer" | "referrer" => options.referrer = Some(value.to_string()),
+        _ => {
+            options.headers.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+fn apply_vlc_option(options: &mut StreamRequestOptions, directive: &str) {
+    let Some((key, value)) = directive.split_once('=') else {
+        return;
+    };
+    let key = key.trim().to_lowercase();
+    let value = unquote(value);
+
+    match key.as_str() {
+        "http-user-agent" => options.user_agent = Some(value.to_string()),
+        "http-referrer" | "http-referer" => options.referrer = Some(value.to_string()),
+        _ => {
+            options.vlc_options.insert(key, value.to_string());
+        }
+    }
+}
+
+fn apply_http_headers(options: &mut StreamRequestOptions, directive: &str) {
+    let Ok(headers) = serde_json::from_str::<HashMap<String, serde_json::Value>>(directive.trim()) else {
+        return;
+    };
+    for (key, value) in headers {
+        let value = match value {
+            serde_json::Value::String(s) => s,
+            other => other.to_string(),
+        };
+        set_header(options, &key, &value);
+    }
+}
+
+fn apply_kodi_prop(options: &mut StreamRequestOptions, directive: &str) {
+    let Some((key, value)) = directive.split_once('=') else {
+        return;
+    };
+    let key = key.trim().to_string();
+    let value = unquote(value);
+
+    // inputstream.adaptive carries request headers as `Name=value&Name=value`
+    if key == "inputstream.adaptive.stream_headers" || key == "inputstream.adaptive.manifest_headers" {
+        for pair in value.split('&') {
+            if let Some((name, val)) = pair.split_once('=') {
+                set_header(options, name.trim(), val.trim());
+            }
+        }
+    }
+
+    options.kodi_props.insert(key, value.to_string());
+}
+
+/// Fold a `#`-prefixed directive line into the pending stream options.
+/// Unrecognised lines are ignored.
+pub fn apply_directive(options: &mut StreamRequestOptions, line: &str) {
+    if let Some(rest) = line.strip_prefix("#EXTVLCOPT:") {
+        apply_vlc_option(options, rest);
+    } else if let Some(rest) = line.strip_prefix("#EXTHTTP:") {
+        apply_http_headers(options, rest);
+    } else if let Some(rest) = line.strip_prefix("#KODIPROP:") {
+        apply_kodi_prop(options, rest);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+/// Result of tokenizing a single `#EXTINF` line.
+#[derive(Debug, Default)]
+pub struct ExtInf {
+    pub duration: Option<f64>,
+    pub attributes: HashMap<String, String>,
+    pub title: Option<String>,
+}
+
+/// Tokenize an `#EXTINF:<duration> key="value" ...,<title>` line.
+///
+/// Attribute values may be double-quoted, single-quoted or bare. Commas inside
+/// quoted values do not terminate the attribute list; the first comma outside
+/// quotes starts the title. Attribute keys are lowercased.
+pub fn parse_extinf(line: &str) -> ExtInf {
+    let rest = line.strip_prefix("#EXTINF:").unwrap_or(line);
+    let chars: Vec<char> = rest.chars().collect();
+    let mut pos = 0;
+    let mut info = ExtInf::default();
+
+    // Duration runs until the first whitespace or comma
+    while pos < chars.len() && !chars[pos].is_whitespace() && chars[pos] != ',' {
+        pos += 1;
+    }
+    let duration: String = chars[..pos].iter().collect();
+    info.duration = duration.trim().parse::<f64>().ok();
+
+    info.title = parse_attributes(&chars[pos..], &mut info.attributes);
+
+    info
+}
+
+/// Parse attributes from an `#EXTM3U` header line.
+pub fn parse_header(line: &str) -> HashMap<String, String> {
+    let rest = line.strip_prefix("#EXTM3U").unwrap_or(line);
+    let chars: Vec<char> = rest.chars().collect();
+    let mut attributes = HashMap::new();
+    parse_attributes(&chars, &mut attributes);
+    attributes
+}
+
+/// Read `key=value` pairs until the end of the line or the
+/// first comma outside quotes, returning the text after that comma.
+fn parse_attributes(chars: &[char], attributes: &mut HashMap<String, String>) -> Option<String> {
+    let mut pos = 0;
+    loop {
+        while pos < chars.len() && chars[pos].is_whitespace() {
+            pos += 1;
+        }
+        if pos >= chars.len() {
+            break;
+        }
+        if chars[pos] == ',' {
+            let title: String = chars[pos + 1..].iter().collect();
+            let title = title.trim();
+            if !title.is_empty() {
+                return Some(title.to_string());
+            }
+            return None;
+        }
+
+        // Attribute key
+        let key_start = pos;
+        while pos < chars.len()
+            && chars[pos] != '='
+            && chars[pos] != ','
+            && !chars[pos].is_whitespace()
+        {
+            pos += 1;
+        }
+        let key: String = chars[key_start..pos].iter().collect::<String>().to_lowercase();
+
+        // Bare flag without a value
+        if pos >= chars.len() || chars[pos] != '=' {
+            if !key.is_empty() {
+                attributes.insert(key, String::new());
+            }
+            continue;
+        }
+        pos += 1;
+
+        // Attribute value
+        let value = if pos < chars.len() && (chars[pos] == '"' || chars[pos] == '\'') {
+            let quote = chars[pos];
+            pos += 1;
+            let value_start = pos;
+            while pos < chars.len() && chars[pos] != quote {
+                pos += 1;
+            }
+            let value: String = chars[value_start..pos].iter().collect();
+            // Skip the closing quote if present
+            if pos < chars.len() {
+                pos += 1;
+            }
+            value
+        } else {
+            let value_start = pos;
+            while pos < chars.len() && chars[pos] != ',' && !chars[pos].is_whitespace() {
+                pos += 1;
+            }
+            chars[value_start..pos].iter().collect()
+        };
+
+        if !key.is_empty() {
+            attributes.insert(key, value.trim().to_string());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_extinf, parse_header};
+
+    #[test]
+    fn single_quoted_values() {
+        let info = parse_extinf("#EXTINF:-1 tvg-name='Say \"Hi\"' tvg-id='one.us',One");
+        assert_eq!(info.attributes["tvg-name"], "Say \"Hi\"");
+        assert_eq!(info.attributes["tvg-id"], "one.us");
+        assert_eq!(info.title.as_deref(), Some("One"));
+    }
+
+    #[test]
+    fn unquoted_values() {
+        let info = parse_extinf("#EXTINF:3600 tvg-chno=7 radio=true TVG-ID=two.uk,Two");
+        assert_eq!(info.duration, Some(3600.0));
+        assert_eq!(info.attributes["tvg-chno"], "7");
+        assert_eq!(info.attributes["radio"], "true");
+        assert_eq!(info.attributes["tvg-id"], "two.uk");
+        assert_eq!(info.title.as_deref(), Some("Two"));
+    }
+
+    #[test]
+    fn commas_inside_quoted_values() {
+        let info = parse_extinf("#EXTINF:-1 group-title=\"News, World\" tvg-name='A, B',Three");
+        assert_eq!(info.attributes["group-title"], "News, World");
+        assert_eq!(info.attributes["tvg-name"], "A, B");
+        assert_eq!(info.title.as_deref(), Some("Three"));
+    }
+
+    #[test]
+    fn title_is_everything_after_the_first_unquoted_comma() {
+        let info = parse_extinf("#EXTINF:-1 tvg-id=\"x\",Movie, The (2001), Part 1");
+        assert_eq!(info.title.as_deref(), Some("Movie, The (2001), Part 1"));
+
+        let info = parse_extinf("#EXTINF:-1,  Spaced Title  ");
+        assert_eq!(info.duration, Some(-1.0));
+        assert!(info.attributes.is_empty());
+        assert_eq!(info.title.as_deref(), Some("Spaced Title"));
+
+        assert_eq!(parse_extinf("#EXTINF:-1 tvg-id=\"x\",").title, None);
+    }
+
+    #[test]
+    fn header_attributes() {
+        let attributes = parse_header("#EXTM3U url-tvg=\"http://a/epg.xml,http://b/epg.xml\" tvg-shift=2");
+        assert_eq!(attributes["url-tvg"], "http://a/epg.xml,http://b/epg.xml");
+        assert_eq!(attributes["tvg-shift"], "2");
+    }
+}
@@ -0,0 +1,96 @@
+use uuid::Uuid;
+
+/// Namespace for channel IDs derived with UUID v5
+const CHANNEL_NAMESPACE: Uuid = Uuid::from_u128(0x6f1c_2a4e_8d3b_4c57_9e21_5a0f_b7d4_c913);
+
+fn normalize(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Deterministic channel ID from normalized name, URL and group.
+///
+/// The ID only depends on the entry itself, never on its position in the
+/// playlist, so it survives providers reordering the file.
+pub fn stable_channel_id(name: &str, url: &str, group: &str) -> String {
+    let key = format!("{}\n{}\n{}", normalize(name), url.trim(), normalize(group));
+    Uuid::new_v5(&CHANNEL_NAMESPACE, key.as_bytes())
+        .simple()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stable_channel_id;
+    use crate::playlist::parse_m3u_content;
+
+    const PLAYLIST: &str = "#EXTM3U
+#EXTINF:-1 tvg-id=\"news.us\" group-title=\"News\",News HD
+http://example.com/news-hd
+#EXTINF:-1 tvg-id=\"news.us\" group-title=\"News\",News SD
+http://example.com/news-sd
+#EXTINF:-1 group-title=\"Sports\",Sports One
+http://example.com/sports
+";
+
+    const REORDERED: &str = "#EXTM3U
+#EXTINF:-1 group-title=\"Sports\",Sports One
+http://example.com/sports
+#EXTINF:-1 tvg-id=\"news.us\" group-title=\"News\",News SD
+http://example.com/news-sd
+#EXTINF:-1 tvg-id=\"news.us\" group-title=\"News\",News HD
+http://example.com/news-hd
+";
+
+    fn id_for(content: &str, name: &str) -> String {
+        parse_m3u_content(content)
+            .items
+            .into_iter()
+            .find(|item| item.name == name)
+            .map(|item| item.id)
+            .unwrap()
+    }
+
+    #[test]
+    fn ids_survive_reordering() {
+        for name in ["News HD", "News SD", "Sports One"] {
+            assert_eq!(id_for(PLAYLIST, name), id_for(REORDERED, name));
+        }
+    }
+
+    #[test]
+    fn shared_tvg_id_does_not_collide() {
+        let playlist = parse_m3u_content(PLAYLIST);
+        assert_ne!(playlist.items[0].id, playlist.items[1].id);
+        assert_eq!(playlist.items[0].tvg_id.as_deref(), Some("news.us"));
+        assert_eq!(playlist.items[1].tvg_id.as_deref(), Some("news.us"));
+    }
+
+    #[test]
+    fn normalization_ignores_case_and_spacing() {
+        assert_eq!(
+            stable_channel_id("News  HD", "http://example.com/news-hd", "News"),
+            stable_channel_id("news hd", " http://example.com/news-hd ", "NEWS"),
+        );
+        assert_ne!(
+            stable_channel_id("News HD", "http://example.com/news-hd", "News"),
+            stable_channel_id("News HD", "http://example.com/news-hd", "Backup"),
+        );
+    }
+
+    #[test]
+    fn exact_duplicates_get_distinct_ids() {
+        let content = "#EXTM3U
+#EXTINF:-1,Dup
+http://example.com/dup
+#EXTINF:-1,Dup
+http://example.com/dup
+";
+        let playlist = parse_m3u_content(content);
+        assert_eq!(playlist.items.len(), 2);
+        assert_ne!(playlist.items[0].id, playlist.items[1].id);
+    }
+}
@@ -1,96 +1,216 @@
-use regex::Regex;
-use crate::types::{PlaylistItem, ParsedPlaylist};
+mod directives;
+mod extinf;
+mod identity;
+mod parser;
+mod source;
+mod writer;
+
+use std::collections::{HashMap, HashSet};
+use futures_util::StreamExt;
+use tauri::Emitter;
+
+use crate::db;
+use crate::types::{
+    CacheProgress, CacheSummary, CachedCategory, CachedChannel, PlaylistHeader, PlaylistItem, ParsedPlaylist,
+    ParsedPlaylistReport, PlaylistLoadProgress,
+};
+
+pub use parser::M3uParser;
+pub use source::{
+    fetch_playlist_if_changed, load_playlist_source, local_path, load_playlist_with_report, read_playlist_header, PlaylistBody,
+    SourceFetch,
+};
+pub use writer::{export_m3u, write_channels_m3u, write_m3u};
+
+fn into_playlist(items: Vec<PlaylistItem>, header: PlaylistHeader) -> ParsedPlaylist {
+    let categories_set: HashSet<&str> = items.iter().map(|item| item.group_title.as_str()).collect();
+    let mut categories: Vec<String> = categories_set.into_iter().map(String::from).collect();
+    categories.sort();
+    
+    ParsedPlaylist { items, categories, header }
+}
 
 pub fn parse_m3u_content(content: &str) -> ParsedPlaylist {
+    let mut parser = M3uParser::new();
     let mut items: Vec<PlaylistItem> = Vec::new();
-    let mut categories_set: std::collections::HashSet<String> = std::collections::HashSet::new();
+    for line in content.lines() {
+        parser.push_line(line, &mut |item| items.push(item));
+    }
+    parser.finish(&mut |item| items.push(item));
     
-    // Regex patterns for parsing EXTINF lines
-    let tvg_id_regex = Regex::new(r#"tvg-id="([^"]*)""#).unwrap();
-    let tvg_logo_regex = Regex::new(r#"tvg-logo="([^"]*)""#).unwrap();
-    let group_title_regex = Regex::new(r#"group-title="([^"]*)""#).unwrap();
-    let name_regex = Regex::new(r#",(.+)$"#).unwrap();
+    into_playlist(items, parser.into_header())
+}
+
+/// Parse tolerating a BOM, a missing `#EXTM3U` header and bare URL lines,
+/// returning a report of everything that was skipped or looked wrong.
+pub fn parse_m3u_lenient(content: &str) -> ParsedPlaylistReport {
+    let mut parser = M3uParser::lenient();
+    let mut items: Vec<PlaylistItem> = Vec::new();
+    for line in content.split('\n') {
+        parser.push_line(line, &mut |item| items.push(item));
+    }
+    parser.finish(&mut |item| items.push(item));
     
-    let lines: Vec<&str> = content.lines().collect();
-    let mut i = 0;
-    let mut item_index = 0;
+    let (header, report) = parser.into_parts();
+    ParsedPlaylistReport {
+        playlist: into_playlist(items, header),
+        report,
+    }
+}
+
+/// `#EXTINF` attributes kept in the cache: the VOD details of an entry, and
+/// its `tvg-name` so an export can write it back
+const METADATA_ATTRIBUTES: &[&str] = &[
+    "plot", "description", "genre", "director", "cast", "actors", "year", "release-date", "rating", "duration",
+    "tvg-duration", "backdrop", "tvg-name",
+];
+
+/// Cache row for an M3U entry: a live channel keyed by the entry's stable
+/// id, in the category named after its group title.
+fn cached_channel(item: PlaylistItem) -> CachedChannel {
+    let mut attributes: HashMap<String, String> = item
+        .attributes
+        .into_iter()
+        .filter(|(key, value)| METADATA_ATTRIBUTES.contains(&key.as_str()) && !value.is_empty())
+        .collect();
+    // The `#EXTINF` duration is the runtime of VOD entries (-1 for live ones)
+    if let Some(duration) = item.duration.filter(|d| *d > 0.0) {
+        attributes.entry("duration".to_string()).or_insert_with(|| duration.to_string());
+    }
     
-    while i < lines.len() {
-        let line = lines[i].trim();
+    CachedChannel {
+        id: item.id,
+        name: item.name,
+        url: item.url,
+        logo: item.tvg_logo,
+        group_title: Some(item.group_title.clone()),
+        content_type: "live".to_string(),
+        category_id: Some(item.group_title),
+        stream_id: None,
+        container_extension: None,
+        tvg_id: item.tvg_id,
+        tvg_shift: item.tvg_shift,
+        channel_number: item.tvg_chno,
+        rating: None,
+        added_at: None,
+        attributes,
+    }
+}
+
+// Channels parsed since the last staged batch, and every group seen so far
+#[derive(Default)]
+struct Parsed {
+    groups: HashSet<String>,
+    pending: Vec<CachedChannel>,
+    items: usize,
+}
+
+impl Parsed {
+    fn push(&mut self, item: PlaylistItem) {
+        self.items += 1;
+        if !self.groups.contains(&item.group_title) {
+            self.groups.insert(item.group_title.clone());
+        }
+        self.pending.push(cached_channel(item));
+    }
+}
+
+/// Parses an M3U playlist straight into the cache, staging its channels in
+/// batches as they're parsed instead of collecting the whole playlist.
+pub struct M3uCache<'a> {
+    parser: M3uParser,
+    writer: db::CacheWriter<'a>,
+    parsed: Parsed,
+}
+
+impl<'a> M3uCache<'a> {
+    pub async fn start(
+        playlist_id: String,
+        on_progress: &'a (dyn Fn(CacheProgress) + Send + Sync),
+    ) -> Result<Self, String> {
+        Ok(Self {
+            parser: M3uParser::new(),
+            writer: db::CacheWriter::start(playlist_id, on_progress).await?,
+            parsed: Parsed::default(),
+        })
+    }
+    
+    pub fn items_parsed(&self) -> usize {
+        self.parsed.items
+    }
+    
+    /// Parse the next chunk of raw bytes, staging channels whenever a
+    /// batch is full.
+    pub async fn feed(&mut self, chunk: &[u8]) -> Result<(), String> {
+        self.parser.feed(chunk, &mut |item| self.parsed.push(item));
         
-        if line.starts_with("#EXTINF:") {
-            // Parse the EXTINF line
-            let mut tvg_id: Option<String> = None;
-            let mut tvg_logo: Option<String> = None;
-            let mut group_title = "Uncategorized".to_string();
-            let mut name = format!("Channel {}", item_index + 1);
-            
-            if let Some(caps) = tvg_id_regex.captures(line) {
-                if let Some(m) = caps.get(1) {
-                    let val = m.as_str().to_string();
-                    if !val.is_empty() {
-                        tvg_id = Some(val);
-                    }
-                }
-            }
-            
-            if let Some(caps) = tvg_logo_regex.captures(line) {
-                if let Some(m) = caps.get(1) {
-                    let val = m.as_str().to_string();
-                    if !val.is_empty() {
-                        tvg_logo = Some(val);
-                    }
-                }
-            }
-            
-            if let Some(caps) = group_title_regex.captures(line) {
-                if let Some(m) = caps.get(1) {
-                    let val = m.as_str().to_string();
-                    if !val.is_empty() {
-                        group_title = val;
-                    }
-                }
-            }
-            
-            if let Some(caps) = name_regex.captures(line) {
-                if let Some(m) = caps.get(1) {
-                    name = m.as_str().trim().to_string();
-                }
-            }
-            
-            // Next non-empty, non-comment line should be the URL
-            i += 1;
-            while i < lines.len() {
-                let url_line = lines[i].trim();
-                if !url_line.is_empty() && !url_line.starts_with('#') {
-                    categories_set.insert(group_title.clone());
-                    
-                    items.push(PlaylistItem {
-                        id: tvg_id.clone().unwrap_or_else(|| format!("item-{}", item_index)),
-                        name,
-                        url: url_line.to_string(),
-                        tvg_id,
-                        tvg_logo,
-                        group_title,
-                    });
-                    item_index += 1;
-                    break;
-                }
-                i += 1;
-            }
+        if self.parser.has_header() == Some(false) {
+            self.writer.discard().await;
+            return Err("Source does not appear to be a valid M3U file".to_string());
         }
-        i += 1;
+        if self.parsed.pending.len() >= db::CACHE_CHUNK_SIZE {
+            self.writer.add_channels(std::mem::take(&mut self.parsed.pending)).await?;
+        }
+        
+        Ok(())
     }
     
-    let mut categories: Vec<String> = categories_set.into_iter().collect();
-    categories.sort();
+    /// Stage what is left and apply the whole playlist to the cache. Returns
+    /// the playlist header too, for its guide URLs.
+    pub async fn finish(mut self) -> Result<(CacheSummary, PlaylistHeader), String> {
+        self.parser.finish(&mut |item| self.parsed.push(item));
+        
+        if self.parser.has_header() != Some(true) {
+            self.writer.discard().await;
+            return Err("Source does not appear to be a valid M3U file".to_string());
+        }
+        if !self.parsed.pending.is_empty() {
+            self.writer.add_channels(std::mem::take(&mut self.parsed.pending)).await?;
+        }
+        
+        let categories = self
+            .parsed
+            .groups
+            .into_iter()
+            .map(|group| CachedCategory {
+                id: group.clone(),
+                name: group,
+                content_type: "live".to_string(),
+            })
+            .collect();
+        let summary = self.writer.finish(categories).await?;
+        
+        Ok((summary, self.parser.into_header()))
+    }
     
-    ParsedPlaylist { items, categories }
+    /// Drop everything staged so far, e.g. when the download failed
+    pub async fn discard(mut self) {
+        self.writer.discard().await;
+    }
 }
 
-pub async fn fetch_and_parse_m3u(url: &str) -> Result<ParsedPlaylist, String> {
-    println!("Rust: Fetching M3U from: {}", url);
-    
+/// Cache an M3U playlist as its body streams in, so only one batch of
+/// channels is held at a time.
+pub async fn cache_m3u(
+    playlist_id: String,
+    mut body: PlaylistBody,
+    on_progress: &(dyn Fn(CacheProgress) + Send + Sync),
+) -> Result<(CacheSummary, PlaylistHeader), String> {
+    let mut cache = M3uCache::start(playlist_id, on_progress).await?;
+    loop {
+        match body.next_chunk().await {
+            Ok(Some(chunk)) => cache.feed(&chunk).await?,
+            Ok(None) => break,
+            Err(e) => {
+                cache.discard().await;
+                return Err(e);
+            }
+        }
+    }
+    cache.finish().await
+}
+
+async fn get_m3u(url: &str) -> Result<reqwest::Response, String> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(60))
         .build()
@@ -107,19 +227,113 @@ pub async fn fetch_and_parse_m3u(url: &str) -> Result<ParsedPlaylist, String> {
         return Err(format!("HTTP error: {}", response.status()));
     }
     
-    let content = response
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+    Ok(response)
+}
+
+fn emit_load_progress(
+    app: &tauri::AppHandle,
+    url: &str,
+    bytes_read: u64,
+    total_bytes: Option<u64>,
+    items_parsed: usize,
+    status: &str,
+) {
+    let progress = PlaylistLoadProgress {
+        url: url.to_string(),
+        bytes_read,
+        total_bytes,
+        items_parsed,
+        status: status.to_string(),
+    };
+    let _ = app.emit("playlist-progress", &progress);
+}
+
+pub async fn fetch_and_parse_m3u(app: &tauri::AppHandle, url: &str) -> Result<ParsedPlaylist, String> {
+    println!("Rust: Fetching M3U from: {}", url);
+    
+    let response = get_m3u(url).await?;
+    let total_bytes = response.content_length();
+    let mut stream = response.bytes_stream();
+    let mut parser = M3uParser::new();
+    let mut items: Vec<PlaylistItem> = Vec::new();
+    let mut bytes_read: u64 = 0;
+    let mut last_emit_time = std::time::Instant::now();
     
-    println!("Rust: Received {} bytes", content.len());
+    let emit_progress = |bytes_read: u64, items_parsed: usize, status: &str| {
+        emit_load_progress(app, url, bytes_read, total_bytes, items_parsed, status);
+    };
+    
+    while let Some(chunk_result) = stream.next().await {
+        let chunk = chunk_result.map_err(|e| format!("Failed to read response: {}", e))?;
+        bytes_read += chunk.len() as u64;
+        parser.feed(&chunk, &mut |item| items.push(item));
+        
+        if parser.has_header() == Some(false) {
+            return Err("Response does not appear to be a valid M3U file".to_string());
+        }
+        
+        // Emit progress every 250ms
+        let now = std::time::Instant::now();
+        if now.duration_since(last_emit_time).as_millis() >= 250 {
+            emit_progress(bytes_read, items.len(), "loading");
+            last_emit_time = now;
+        }
+    }
+    parser.finish(&mut |item| items.push(item));
     
-    if !content.trim().starts_with("#EXTM3U") {
+    if parser.has_header() != Some(true) {
         return Err("Response does not appear to be a valid M3U file".to_string());
     }
     
-    let result = parse_m3u_content(&content);
+    println!("Rust: Received {} bytes", bytes_read);
+    emit_progress(bytes_read, items.len(), "completed");
+    
+    let result = into_playlist(items, parser.into_header());
     println!("Rust: Parsed {} items in {} categories", result.items.len(), result.categories.len());
     
     Ok(result)
 }
+
+/// Stream an M3U playlist from a URL into the playlist's cache, writing
+/// channels in batches while the download is still running.
+pub async fn fetch_and_cache_m3u(
+    app: &tauri::AppHandle,
+    playlist_id: String,
+    url: &str,
+) -> Result<CacheSummary, String> {
+    println!("Rust: Fetching M3U {} into playlist {}", url, playlist_id);
+    
+    let response = get_m3u(url).await?;
+    let total_bytes = response.content_length();
+    let mut stream = response.bytes_stream();
+    let on_progress = |progress: CacheProgress| {
+        let _ = app.emit("cache-progress", &progress);
+    };
+    let mut cache = M3uCache::start(playlist_id, &on_progress).await?;
+    let mut bytes_read: u64 = 0;
+    let mut last_emit_time = std::time::Instant::now();
+    
+    while let Some(chunk_result) = stream.next().await {
+        let chunk = match chunk_result {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                cache.discard().await;
+                return Err(format!("Failed to read response: {}", e));
+            }
+        };
+        bytes_read += chunk.len() as u64;
+        cache.feed(&chunk).await?;
+        
+        // Emit progress every 250ms
+        let now = std::time::Instant::now();
+        if now.duration_since(last_emit_time).as_millis() >= 250 {
+            emit_load_progress(app, url, bytes_read, total_bytes, cache.items_parsed(), "loading");
+            last_emit_time = now;
+        }
+    }
+    let items_parsed = cache.items_parsed();
+    let (summary, _) = cache.finish().await?;
+    emit_load_progress(app, url, bytes_read, total_bytes, items_parsed, "completed");
+    
+    Ok(summary)
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use crate::types::{ParseIssue, ParseReport, PlaylistHeader, PlaylistItem, StreamRequestOptions};
+
+use super::directives::apply_directive;
+use super::extinf::{parse_extinf, parse_header, ExtInf};
+use super::identity::stable_channel_id;
+
+fn non_empty(attributes: &HashMap<String, String>, key: &str) -> Option<String> {
+    attributes
+        .get(key)
+        .filter(|v| !v.is_empty())
+        .cloned()
+}
+
+fn parse_flag(attributes: &HashMap<String, String>, key: &str) -> bool {
+    matches!(
+        attributes.get(key).map(|v| v.to_lowercase()).as_deref(),
+        Some("true") | Some("1") | Some("yes")
+    )
+}
+
+/// Build the playlist header from the `#EXTM3U` line's attributes.
+fn build_header(attributes: HashMap<String, String>) -> PlaylistHeader {
+    let mut epg_urls: Vec<String> = Vec::new();
+    for key in ["url-tvg", "x-tvg-url"] {
+        if let Some(value) = attributes.get(key) {
+            for url in value.split(',').map(str::trim).filter(|u| !u.is_empty()) {
+                if !epg_urls.iter().any(|u| u == url) {
+                    epg_urls.push(url.to_string());
+                }
+            }
+        }
+    }
+
+    PlaylistHeader {
+        epg_urls,
+        tvg_shift: non_empty(&attributes, "tvg-shift").and_then(|v| v.parse().ok()),
+        catchup: non_empty(&attributes, "catchup"),
+        catchup_days: non_empty(&attributes, "catchup-days").and_then(|v| v.parse().ok()),
+        catchup_source: non_empty(&attributes, "catchup-source"),
+        attributes,
+    }
+}
+
+/// Build a `PlaylistItem` from a tokenized `#EXTINF` line and its stream URL.
+///
+/// `extgrp` is the group from a preceding `#EXTGRP` line and is only used when
+/// the entry has no `group-title`. Header defaults fill in missing shift and
+/// catchup attributes.
+fn build_item(
+    info: ExtInf,
+    url: &str,
+    request_options: StreamRequestOptions,
+    extgrp: Option<String>,
+    header: &PlaylistHeader,
+    item_index: usize,
+) -> PlaylistItem {
+    let attrs = info.attributes;
+
+    let name = info
+        .title
+        .or_else(|| non_empty(&attrs, "tvg-name"))
+        .unwrap_or_else(|| format!("Channel {}", item_index + 1));
+
+    let group_title = non_empty(&attrs, "group-title")
+        .or(extgrp)
+        .unwrap_or_else(|| "Uncategorized".to_string());
+
+    PlaylistItem {
+        id: stable_channel_id(&name, url, &group_title),
+        name,
+        url: url.to_string(),
+        duration: info.duration,
+        tvg_id: non_empty(&attrs, "tvg-id"),
+        tvg_name: non_empty(&attrs, "tvg-name"),
+        tvg_logo: non_empty(&attrs, "tvg-logo"),
+        tvg_chno: non_empty(&attrs, "tvg-chno").and_then(|v| v.parse().ok()),
+        tvg_shift: non_empty(&attrs, "tvg-shift")
+            .and_then(|v| v.parse().ok())
+            .or(header.tvg_shift),
+        tvg_country: non_empty(&attrs, "tvg-country"),
+        tvg_language: non_empty(&attrs, "tvg-language"),
+        tvg_rec: non_empty(&attrs, "tvg-rec").and_then(|v| v.parse().ok()),
+        group_title,
+        catchup: non_empty(&attrs, "catchup").or_else(|| header.catchup.clone()),
+        catchup_days: non_empty(&attrs, "catchup-days")
+            .and_then(|v| v.parse().ok())
+            .or(header.catchup_days),
+        catchup_source: non_empty(&attrs, "catchup-source").or_else(|| header.catchup_source.clone()),
+        radio: parse_flag(&attrs, "radio"),
+        attributes: attrs,
+        request_options,
+    }
+}
+
+/// Incremental M3U parser.
+///
+/// Lines (or raw byte chunks) are pushed in as they arrive and each
+/// `PlaylistItem` is emitted as soon as its URL line has been seen, so the
+/// whole playlist never has to be held in memory as text. Skipped and
+/// malformed entries are recorded in a `ParseReport` as parsing goes.
+#[derive(Default)]
+pub struct M3uParser {
+    header: PlaylistHeader,
+    pending: Option<(ExtInf, usize)>,
+    options: StreamRequestOptions,
+    extgrp: Option<String>,
+    item_index: usize,
+    seen_ids: HashMap<String, usize>,
+    has_header: Option<bool>,
+    buffer: Vec<u8>,
+    lenient: bool,
+    line_number: usize,
+    report: ParseReport,
+}
+
+impl M3uParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A parser that also accepts headerless files and bare URL lines
+    /// without a preceding `#EXTINF`.
+    pub fn lenient() -> Self {
+        Self {
+            lenient: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn header(&self) -> &PlaylistHeader {
+        &self.header
+    }
+
+    pub fn report(&self) -> &ParseReport {
+        &self.report
+    }
+
+    pub fn into_header(self) -> PlaylistHeader {
+        self.header
+    }
+
+    pub fn into_parts(self) -> (PlaylistHeader, ParseReport) {
+        (self.header, self.report)
+    }
+
+    /// Whether the first non-empty line was `#EXTM3U`, or `None` before any
+    /// content has been seen.
+    pub fn has_header(&self) -> Option<bool> {
+        self.has_header
+    }
+
+    fn warn(&mut self, line: usize, message: impl Into<String>) {
+        self.report.warnings.push(ParseIssue { line, message: message.into() });
+    }
+
+    fn skip(&mut self, line: usize, message: impl Into<String>) {
+        self.report.skipped.push(ParseIssue { line, message: message.into() });
+    }
+
+    /// Drop an `#EXTINF` that never got its URL, together with the directives
+    /// and `#EXTGRP` collected for it, so they don't attach to the next entry.
+    fn discard_pending(&mut self) {
+        if let Some((_, pending_line)) = self.pending.take() {
+            self.skip(pending_line, "#EXTINF without a stream URL");
+            self.options = StreamRequestOptions::default();
+            self.extgrp = None;
+        }
+    }
+
+    /// Feed a single line, handing the completed item to `on_item` if it was a URL.
+    pub fn push_line(&mut self, line: &str, on_item: &mut impl FnMut(PlaylistItem)) {
+        self.line_number += 1;
+        self.report.lines_read = self.line_number;
+        let line_number = self.line_number;
+
+        let mut line = line;
+        if line_number == 1 {
+            if let Some(stripped) = line.strip_prefix('\u{feff}') {
+                self.warn(line_number, "Stripped UTF-8 byte order mark");
+                line = stripped;
+            }
+        }
+
+        // Bare carriage returns used as line breaks
+        let line = line.trim_end_matches('\r');
+        if line.contains('\r') {
+            self.warn(line_number, "Bare carriage return used as a line break");
+            for part in line.split('\r') {
+                self.push_trimmed(part.trim(), line_number, on_item);
+            }
+        } else {
+            self.push_trimmed(line.trim(), line_number, on_item);
+        }
+    }
+
+    fn push_trimmed(&mut self, line: &str, line_number: usize, on_item: &mut impl FnMut(PlaylistItem)) {
+        if line.is_empty() {
+            return;
+        }
+        if self.has_header.is_none() {
+            let has_header = line.starts_with("#EXTM3U");
+            if !has_header {
+                self.warn(line_number, "Missing #EXTM3U header");
+            }
+            self.has_header = Some(has_header);
+        }
+
+        if line.starts_with("#EXTINF:") {
+            self.discard_pending();
+            let info = parse_extinf(line);
+            if info.duration.is_none() {
+                self.warn(line_number, "Malformed #EXTINF duration");
+            }
+            if info.title.is_none() {
+                self.warn(line_number, "#EXTINF has no title");
+            }
+            self.pending = Some((info, line_number));
+        } else if line.starts_with("#EXTM3U") {
+            self.header = build_header(parse_header(line));
+        } else if let Some(group) = line.strip_prefix("#EXTGRP:") {
+            let group = group.trim();
+            if !group.is_empty() {
+                self.extgrp = Some(group.to_string());
+            }
+        } else if line.starts_with('#') {
+            // Per-entry directives may appear before or after #EXTINF
+            apply_directive(&mut self.options, line);
+        } else {
+            // A URL closes the current entry; directives never carry over
+            let request_options = std::mem::take(&mut self.options);
+            let group = self.extgrp.take();
+            let info = match self.pending.take() {
+                Some((info, _)) => info,
+                None if self.lenient => {
+                    self.warn(line_number, "Stream URL without #EXTINF");
+                    ExtInf::default()
+                }
+                None => {
+                    self.skip(line_number, "Stream URL without #EXTINF");
+                    return;
+                }
+            };
+            let mut item = build_item(info, line, request_options, group, &self.header, self.item_index);
+            self.item_index += 1;
+
+            // Exact duplicates (same name, URL and group) get a numbered suffix
+            let occurrences = self.seen_ids.entry(item.id.clone()).or_insert(0);
+            *occurrences += 1;
+            if *occurrences > 1 {
+                item.id = format!("{}-{}", item.id, occurrences);
+            }
+            self.report.items_parsed += 1;
+            on_item(item);
+        }
+    }
+
+    /// Feed a chunk of raw bytes, handing every item completed by it to `on_item`
+    /// as soon as it is parsed.
+    /// A trailing partial line is kept until the next chunk or `finish`.
+    pub fn feed(&mut self, chunk: &[u8], on_item: &mut impl FnMut(PlaylistItem)) {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut start = 0;
+        while let Some(offset) = self.buffer[start..].iter().position(|b| *b == b'\n') {
+            let end = start + offset;
+            let line = String::from_utf8_lossy(&self.buffer[start..end]).into_owned();
+            self.push_line(&line, on_item);
+            start = end + 1;
+        }
+        self.buffer.drain(..start);
+    }
+
+    /// Flush the last line if the input did not end with a newline and
+    /// record a trailing `#EXTINF` that never got its URL.
+    pub fn finish(&mut self, on_item: &mut impl FnMut(PlaylistItem)) {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            let line = String::from_utf8_lossy(&buffer).into_owned();
+            self.push_line(&line, on_item);
+        }
+        self.discard_pending();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::playlist::parse_m3u_lenient;
+
+    #[test]
+    fn orphaned_extinf_does_not_leak_options() {
+        let content = "#EXTM3U
+#EXTINF:-1,Orphan
+#EXTGRP:Orphan Group
+#EXTVLCOPT:http-user-agent=Orphan/1.0
+#EXTHTTP:{\"cookie\":\"a=b\"}
+#EXTINF:-1,Next
+http://example.com/next
+";
+        let result = parse_m3u_lenient(content);
+        assert_eq!(result.playlist.items.len(), 1);
+        let item = &result.playlist.items[0];
+        assert_eq!(item.name, "Next");
+        assert_eq!(item.group_title, "Uncategorized");
+        assert_eq!(item.request_options.user_agent, None);
+        assert!(item.request_options.headers.is_empty());
+        assert_eq!(result.report.skipped.len(), 1);
+        assert_eq!(result.report.skipped[0].line, 2);
+    }
+
+    #[test]
+    fn directives_before_extinf_still_apply() {
+        let content = "#EXTM3U
+#EXTGRP:News
+#EXTVLCOPT:http-user-agent=Kept/1.0
+#EXTINF:-1,One
+http://example.com/one
+";
+        let result = parse_m3u_lenient(content);
+        let item = &result.playlist.items[0];
+        assert_eq!(item.group_title, "News");
+        assert_eq!(item.request_options.user_agent.as_deref(), Some("Kept/1.0"));
+    }
+}
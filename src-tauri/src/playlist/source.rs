@@ -0,0 +1,423 @@
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::io::AsyncReadExt;
+
+use crate::types::{ParsedPlaylist, ParsedPlaylistReport, PlaylistHeader};
+use super::{parse_m3u_content, parse_m3u_lenient, M3uParser};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+/// Incremental gzip/bzip2 decompression, fed one chunk at a time
+enum Decoder {
+    Plain,
+    Gzip(Box<flate2::write::MultiGzDecoder<Vec<u8>>>),
+    Bzip2 { stream: bzip2::Decompress, ended: bool },
+}
+
+impl Decoder {
+    /// Pick the decompression from the first bytes of the content or the
+    /// `Content-Encoding` header.
+    fn detect(head: &[u8], content_encoding: Option<&str>) -> Self {
+        let encoding = content_encoding.map(|e| e.trim().to_lowercase());
+        if head.starts_with(GZIP_MAGIC) || matches!(encoding.as_deref(), Some("gzip") | Some("x-gzip")) {
+            Decoder::Gzip(Box::new(flate2::write::MultiGzDecoder::new(Vec::new())))
+        } else if head.starts_with(BZIP2_MAGIC) || matches!(encoding.as_deref(), Some("bzip2") | Some("x-bzip2")) {
+            Decoder::Bzip2 {
+                stream: bzip2::Decompress::new(false),
+                ended: false,
+            }
+        } else {
+            Decoder::Plain
+        }
+    }
+    
+    /// Decompress the next chunk, returning what it decoded to so far.
+    fn write(&mut self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Decoder::Plain => Ok(chunk.to_vec()),
+            Decoder::Gzip(decoder) => {
+                decoder
+                    .write_all(chunk)
+                    .and_then(|_| decoder.flush())
+                    .map_err(|e| format!("Failed to decompress gzip content: {}", e))?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            Decoder::Bzip2 { stream, ended } => {
+                let mut output = Vec::new();
+                let mut input = chunk;
+                loop {
+                    // Concatenated streams go on in a new one
+                    if *ended && !input.is_empty() {
+                        *stream = bzip2::Decompress::new(false);
+                        *ended = false;
+                    }
+                    output.reserve(64 * 1024);
+                    let before = stream.total_in();
+                    let status = stream
+                        .decompress_vec(input, &mut output)
+                        .map_err(|e| format!("Failed to decompress bzip2 content: {}", e))?;
+                    input = &input[(stream.total_in() - before) as usize..];
+                    *ended = status == bzip2::Status::StreamEnd;
+                    // A full buffer may leave output behind
+                    if input.is_empty() && (*ended || output.len() < output.capacity()) {
+                        return Ok(output);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Whatever is still buffered once the content has ended
+    fn finish(self) -> Result<Vec<u8>, String> {
+        match self {
+            Decoder::Plain => Ok(Vec::new()),
+            Decoder::Gzip(decoder) => decoder
+                .finish()
+                .map_err(|e| format!("Failed to decompress gzip content: {}", e)),
+            Decoder::Bzip2 { stream, ended } if !ended && stream.total_in() > 0 => {
+                Err("Failed to decompress bzip2 content: unexpected end of stream".to_string())
+            }
+            Decoder::Bzip2 { .. } => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Decompress gzip or bzip2 content, detected by magic bytes or the
+/// `Content-Encoding` header. Anything else is returned unchanged.
+fn decompress(bytes: Vec<u8>, content_encoding: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut decoder = Decoder::detect(&bytes, content_encoding);
+    if let Decoder::Plain = decoder {
+        return Ok(bytes);
+    }
+    
+    let mut output = decoder.write(&bytes)?;
+    output.extend(decoder.finish()?);
+    Ok(output)
+}
+
+async fn fetch_bytes(url: &str) -> Result<(Vec<u8>, Option<String>), String> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(60))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    
+    let response = client
+        .get(url)
+        .header("User-Agent", "WatchTV/1.0")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch URL: {}", e))?;
+    
+    if !response.status().is_success() {
+        return Err(format!("HTTP error: {}", response.status()));
+    }
+    
+    let content_encoding = response
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    
+    Ok((bytes.to_vec(), content_encoding))
+}
+
+/// Local path of a `file://` URL (percent-decoded, drive letters kept) or of
+/// a plain path, which is returned as is.
+pub fn local_path(source: &str) -> Result<PathBuf, String> {
+    if !source.starts_with("file:") {
+        return Ok(PathBuf::from(source));
+    }
+    reqwest::Url::parse(source)
+        .map_err(|e| format!("Invalid file URL {}: {}", source, e))?
+        .to_file_path()
+        .map_err(|_| format!("Invalid file URL {}", source))
+}
+
+/// Read the raw playlist bytes from an HTTP(S) URL, a `file://` URL or a
+/// plain local path, decompressing gzip/bzip2 content transparently.
+pub async fn read_playlist_source(source: &str) -> Result<Vec<u8>, String> {
+    let source = source.trim();
+    
+    let (bytes, content_encoding) = if source.starts_with("http://") || source.starts_with("https://") {
+        fetch_bytes(source).await?
+    } else {
+        let path = local_path(source)?;
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|e| format!("Failed to read playlist file {}: {}", path.display(), e))?;
+        (bytes, None)
+    };
+    
+    decompress(bytes, content_encoding.as_deref())
+}
+
+enum BodyReader {
+    Http(Box<reqwest::Response>),
+    File(Box<tokio::fs::File>),
+}
+
+/// A playlist read chunk by chunk and decompressed as it arrives, so a
+/// large playlist is never held in memory as a whole.
+pub struct PlaylistBody {
+    reader: BodyReader,
+    content_encoding: Option<String>,
+    // Chosen once the first bytes are in, which show the compression
+    decoder: Option<Decoder>,
+    head: Vec<u8>,
+}
+
+impl PlaylistBody {
+    async fn read_raw(&mut self) -> Result<Option<Vec<u8>>, String> {
+        match &mut self.reader {
+            BodyReader::Http(response) => response
+                .chunk()
+                .await
+                .map(|chunk| chunk.map(|c| c.to_vec()))
+                .map_err(|e| format!("Failed to read response: {}", e)),
+            BodyReader::File(file) => {
+                let mut buf = vec![0; 64 * 1024];
+                let read = file
+                    .read(&mut buf)
+                    .await
+                    .map_err(|e| format!("Failed to read playlist file: {}", e))?;
+                buf.truncate(read);
+                Ok((read > 0).then_some(buf))
+            }
+        }
+    }
+    
+    /// The next decompressed chunk, or `None` once the whole body is read
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, String> {
+        loop {
+            let Some(raw) = self.read_raw().await? else {
+                // A body shorter than a magic number is only detected here
+                let mut decoder = match self.decoder.take() {
+                    Some(decoder) => decoder,
+                    None => Decoder::detect(&self.head, self.content_encoding.as_deref()),
+                };
+                let mut output = decoder.write(&std::mem::take(&mut self.head))?;
+                output.extend(decoder.finish()?);
+                return Ok((!output.is_empty()).then_some(output));
+            };
+            
+            if let Some(decoder) = &mut self.decoder {
+                return decoder.write(&raw).map(Some);
+            }
+            self.head.extend(raw);
+            if self.head.len() >= BZIP2_MAGIC.len() {
+                let mut decoder = Decoder::detect(&self.head, self.content_encoding.as_deref());
+                let output = decoder.write(&std::mem::take(&mut self.head))?;
+                self.decoder = Some(decoder);
+                return Ok(Some(output));
+            }
+        }
+    }
+}
+
+/// Result of a conditional playlist fetch
+pub enum SourceFetch {
+    /// The server answered `304 Not Modified`
+    NotModified,
+    Body {
+        body: PlaylistBody,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// Fetch a playlist, sending `If-None-Match` / `If-Modified-Since` for HTTP
+/// sources so an unchanged playlist isn't downloaded again. Local files are
+/// always read. The body is left to be streamed.
+pub async fn fetch_playlist_if_changed(
+    source: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Result<SourceFetch, String> {
+    let source = source.trim();
+    if !source.starts_with("http://") && !source.starts_with("https://") {
+        let path = local_path(source)?;
+        let file = tokio::fs::File::open(&path)
+            .await
+            .map_err(|e| format!("Failed to read playlist file {}: {}", path.display(), e))?;
+        return Ok(SourceFetch::Body {
+            body: PlaylistBody {
+                reader: BodyReader::File(Box::new(file)),
+                content_encoding: None,
+                decoder: None,
+                head: Vec::new(),
+            },
+            etag: None,
+            last_modified: None,
+        });
+    }
+    
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(60))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    
+    let mut request = client.get(source).header("User-Agent", "WatchTV/1.0");
+    if let Some(etag) = etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+    }
+    
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch URL: {}", e))?;
+    
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(SourceFetch::NotModified);
+    }
+    if !response.status().is_success() {
+        return Err(format!("HTTP error: {}", response.status()));
+    }
+    
+    let header = |name: reqwest::header::HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
+    let content_encoding = header(reqwest::header::CONTENT_ENCODING);
+    
+    Ok(SourceFetch::Body {
+        body: PlaylistBody {
+            reader: BodyReader::Http(Box::new(response)),
+            content_encoding,
+            decoder: None,
+            head: Vec::new(),
+        },
+        etag,
+        last_modified,
+    })
+}
+
+/// Read only the `#EXTM3U` header of a playlist, e.g. to find its `url-tvg`.
+pub async fn read_playlist_header(source: &str) -> Result<PlaylistHeader, String> {
+    let bytes = read_playlist_source(source).await?;
+    let content = String::from_utf8_lossy(&bytes);
+    
+    let mut parser = M3uParser::new();
+    for line in content.lines() {
+        if line.trim_start().starts_with("#EXTINF") {
+            break;
+        }
+        parser.push_line(line, &mut |_| {});
+    }
+    
+    Ok(parser.into_header())
+}
+
+/// Load and parse a playlist from a URL or local file.
+pub async fn load_playlist_source(source: &str) -> Result<ParsedPlaylist, String> {
+    println!("Rust: Loading playlist source: {}", source);
+    
+    let bytes = read_playlist_source(source).await?;
+    let content = String::from_utf8_lossy(&bytes);
+    
+    if !content.trim_start_matches('\u{feff}').trim().starts_with("#EXTM3U") {
+        return Err("Source does not appear to be a valid M3U file".to_string());
+    }
+    
+    let result = parse_m3u_content(&content);
+    println!("Rust: Parsed {} items in {} categories", result.items.len(), result.categories.len());
+    
+    Ok(result)
+}
+
+/// Load a playlist in lenient mode, returning a diagnostics report alongside it.
+pub async fn load_playlist_with_report(source: &str) -> Result<ParsedPlaylistReport, String> {
+    println!("Rust: Loading playlist source with report: {}", source);
+    
+    let bytes = read_playlist_source(source).await?;
+    let content = String::from_utf8_lossy(&bytes);
+    
+    let result = parse_m3u_lenient(&content);
+    println!(
+        "Rust: Parsed {} items, skipped {}, {} warnings",
+        result.report.items_parsed,
+        result.report.skipped.len(),
+        result.report.warnings.len()
+    );
+    
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress, local_path, Decoder};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    const PLAYLIST: &[u8] = b"#EXTM3U\n#EXTINF:-1,One\nhttp://example.com/one\n";
+
+    // Feed the content in small chunks, as a streamed body arrives
+    fn decode_in_chunks(content: &[u8]) -> Vec<u8> {
+        let mut decoder = Decoder::detect(content, None);
+        let mut output = Vec::new();
+        for chunk in content.chunks(7) {
+            output.extend(decoder.write(chunk).unwrap());
+        }
+        output.extend(decoder.finish().unwrap());
+        output
+    }
+
+    #[test]
+    fn gzip_members_decode_in_chunks() {
+        let mut content = Vec::new();
+        for _ in 0..2 {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(PLAYLIST).unwrap();
+            content.extend(encoder.finish().unwrap());
+        }
+        assert_eq!(decode_in_chunks(&content), [PLAYLIST, PLAYLIST].concat());
+        assert_eq!(decompress(content, None).unwrap(), [PLAYLIST, PLAYLIST].concat());
+    }
+
+    #[test]
+    fn bzip2_streams_decode_in_chunks() {
+        let mut content = Vec::new();
+        for _ in 0..2 {
+            let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            encoder.write_all(PLAYLIST).unwrap();
+            content.extend(encoder.finish().unwrap());
+        }
+        assert_eq!(decode_in_chunks(&content), [PLAYLIST, PLAYLIST].concat());
+
+        content.truncate(content.len() - 10);
+        assert!(decompress(content, None).is_err());
+    }
+
+    #[test]
+    fn plain_content_is_unchanged() {
+        assert_eq!(decode_in_chunks(PLAYLIST), PLAYLIST);
+    }
+
+    #[test]
+    fn file_urls_are_decoded() {
+        #[cfg(unix)]
+        assert_eq!(
+            local_path("file:///home/me/My%20Lists/tv.m3u").unwrap(),
+            PathBuf::from("/home/me/My Lists/tv.m3u")
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            local_path("file:///C:/My%20Lists/tv.m3u").unwrap(),
+            PathBuf::from("C:\\My Lists\\tv.m3u")
+        );
+        assert_eq!(local_path("/tmp/a b.m3u").unwrap(), PathBuf::from("/tmp/a b.m3u"));
+    }
+}
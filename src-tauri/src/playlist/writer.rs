@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::types::{CachedChannel, ParsedPlaylist, PlaylistHeader, PlaylistItem, StreamRequestOptions};
+
+/// Known attributes are written first, in this order; anything else follows sorted by key
+const ATTRIBUTE_ORDER: &[&str] = &[
+    "tvg-id",
+    "tvg-name",
+    "tvg-logo",
+    "tvg-chno",
+    "tvg-shift",
+    "tvg-country",
+    "tvg-language",
+    "tvg-rec",
+    "group-title",
+    "catchup",
+    "catchup-days",
+    "catchup-source",
+    "radio",
+];
+
+/// M3U has no escape sequence, so a value holding both kinds of quote keeps
+/// its single quotes and has its double quotes percent-encoded.
+fn quote(value: &str) -> String {
+    if !value.contains('"') {
+        format!("\"{}\"", value)
+    } else if !value.contains('\'') {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value.replace('"', "%22"))
+    }
+}
+
+fn write_attributes(out: &mut String, attributes: &BTreeMap<String, String>) {
+    for key in ATTRIBUTE_ORDER {
+        if let Some(value) = attributes.get(*key) {
+            out.push_str(&format!(" {}={}", key, quote(value)));
+        }
+    }
+    for (key, value) in attributes {
+        if !ATTRIBUTE_ORDER.contains(&key.as_str()) {
+            out.push_str(&format!(" {}={}", key, quote(value)));
+        }
+    }
+}
+
+/// Start from the raw attributes and overlay the typed fields so edits to
+/// either are preserved.
+fn merge_attributes(
+    raw: &HashMap<String, String>,
+    typed: Vec<(&str, Option<String>)>,
+) -> BTreeMap<String, String> {
+    let mut attributes: BTreeMap<String, String> = raw
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    for (key, value) in typed {
+        if let Some(value) = value {
+            attributes.insert(key.to_string(), value);
+        }
+    }
+    attributes
+}
+
+fn write_header(out: &mut String, header: &PlaylistHeader) {
+    let mut typed = vec![
+        ("tvg-shift", header.tvg_shift.map(|v| v.to_string())),
+        ("catchup", header.catchup.clone()),
+        ("catchup-days", header.catchup_days.map(|v| v.to_string())),
+        ("catchup-source", header.catchup_source.clone()),
+    ];
+    if !header.epg_urls.is_empty() && !header.attributes.contains_key("x-tvg-url") {
+        typed.push(("url-tvg", Some(header.epg_urls.join(","))));
+    }
+
+    out.push_str("#EXTM3U");
+    write_attributes(out, &merge_attributes(&header.attributes, typed));
+    out.push('\n');
+}
+
+fn format_duration(duration: Option<f64>) -> String {
+    match duration {
+        Some(d) if d.fract() == 0.0 => format!("{}", d as i64),
+        Some(d) => d.to_string(),
+        None => "-1".to_string(),
+    }
+}
+
+fn write_request_options(out: &mut String, options: &StreamRequestOptions) {
+    if let Some(user_agent) = &options.user_agent {
+        out.push_str(&format!("#EXTVLCOPT:http-user-agent={}\n", user_agent));
+    }
+    if let Some(referrer) = &options.referrer {
+        out.push_str(&format!("#EXTVLCOPT:http-referrer={}\n", referrer));
+    }
+    let vlc_options: BTreeMap<_, _> = options.vlc_options.iter().collect();
+    for (key, value) in vlc_options {
+        out.push_str(&format!("#EXTVLCOPT:{}={}\n", key, value));
+    }
+    let kodi_props: BTreeMap<_, _> = options.kodi_props.iter().collect();
+    for (key, value) in kodi_props {
+        out.push_str(&format!("#KODIPROP:{}={}\n", key, value));
+    }
+    if !options.headers.is_empty() {
+        let headers: BTreeMap<_, _> = options.headers.iter().collect();
+        if let Ok(json) = serde_json::to_string(&headers) {
+            out.push_str(&format!("#EXTHTTP:{}\n", json));
+        }
+    }
+}
+
+/// Drop a value the entry only inherited from a default (the header or the
+/// parser's fallback group) so it is not spelled out on every `#EXTINF` line.
+fn unless_inherited<T: PartialEq + ToString>(
+    item: &PlaylistItem,
+    key: &str,
+    value: Option<T>,
+    header_value: Option<T>,
+) -> Option<String> {
+    if !item.attributes.contains_key(key) && value.is_some() && value == header_value {
+        return None;
+    }
+    value.map(|v| v.to_string())
+}
+
+fn write_item(out: &mut String, item: &PlaylistItem, header: &PlaylistHeader) {
+    let typed = vec![
+        ("tvg-id", item.tvg_id.clone()),
+        ("tvg-name", item.tvg_name.clone()),
+        ("tvg-logo", item.tvg_logo.clone()),
+        ("tvg-chno", item.tvg_chno.map(|v| v.to_string())),
+        ("tvg-shift", unless_inherited(item, "tvg-shift", item.tvg_shift, header.tvg_shift)),
+        ("tvg-country", item.tvg_country.clone()),
+        ("tvg-language", item.tvg_language.clone()),
+        ("tvg-rec", item.tvg_rec.map(|v| v.to_string())),
+        ("group-title", unless_inherited(item, "group-title", Some(item.group_title.as_str()), Some("Uncategorized"))),
+        ("catchup", unless_inherited(item, "catchup", item.catchup.clone(), header.catchup.clone())),
+        ("catchup-days", unless_inherited(item, "catchup-days", item.catchup_days, header.catchup_days)),
+        ("catchup-source", unless_inherited(item, "catchup-source", item.catchup_source.clone(), header.catchup_source.clone())),
+        ("radio", item.radio.then(|| "true".to_string())),
+    ];
+
+    out.push_str("#EXTINF:");
+    out.push_str(&format_duration(item.duration));
+    write_attributes(out, &merge_attributes(&item.attributes, typed));
+    out.push(',');
+    out.push_str(&item.name);
+    out.push('\n');
+    write_request_options(out, &item.request_options);
+    out.push_str(&item.url);
+    out.push('\n');
+}
+
+/// Serialize a parsed playlist back to extended M3U.
+pub fn write_m3u(playlist: &ParsedPlaylist) -> String {
+    let mut out = String::new();
+    write_header(&mut out, &playlist.header);
+    for item in &playlist.items {
+        write_item(&mut out, item, &playlist.header);
+    }
+    out
+}
+
+/// Serialize cached channels to extended M3U, with every cached attribute.
+pub fn write_channels_m3u(channels: &[CachedChannel]) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for channel in channels {
+        let typed = vec![
+            ("tvg-id", channel.tvg_id.clone()),
+            ("tvg-logo", channel.logo.clone()),
+            ("tvg-chno", channel.channel_number.map(|v| v.to_string())),
+            ("tvg-shift", channel.tvg_shift.map(|v| v.to_string())),
+            ("group-title", channel.group_title.clone()),
+        ];
+        let mut attributes = merge_attributes(&channel.attributes, typed);
+        // A runtime cached from the `#EXTINF` duration goes back there
+        let duration = attributes
+            .get("duration")
+            .and_then(|d| d.parse::<f64>().ok())
+            .filter(|d| *d > 0.0);
+        if duration.is_some() {
+            attributes.remove("duration");
+        }
+        out.push_str("#EXTINF:");
+        out.push_str(&format_duration(duration));
+        write_attributes(&mut out, &attributes);
+        out.push(',');
+        out.push_str(&channel.name);
+        out.push('\n');
+        out.push_str(&channel.url);
+        out.push('\n');
+    }
+    out
+}
+
+/// Write a playlist or channel selection to a file on disk.
+pub async fn export_m3u(path: &str, content: String) -> Result<(), String> {
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| format!("Failed to write playlist file: {}", e))?;
+    println!("Exported playlist to {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_channels_m3u, write_m3u};
+    use crate::playlist::{cached_channel, parse_m3u_content};
+    use crate::playlist::extinf::parse_extinf;
+
+    #[test]
+    fn round_trip_preserves_entries() {
+        let content = "#EXTM3U url-tvg=\"http://example.com/epg.xml\" tvg-shift=\"1\"
+#EXTINF:-1 tvg-id=\"one.us\" tvg-name='Say \"Hi\"' tvg-chno=\"7\" group-title=\"News, World\" x-custom=\"a\",One
+#EXTVLCOPT:http-user-agent=Mozilla/5.0
+#EXTHTTP:{\"cookie\":\"a=b\"}
+http://example.com/one
+#EXTINF:3600 radio=\"true\",Two
+http://example.com/two
+";
+        let first = parse_m3u_content(content);
+        let second = parse_m3u_content(&write_m3u(&first));
+
+        assert_eq!(first.header.epg_urls, second.header.epg_urls);
+        assert_eq!(first.items.len(), second.items.len());
+        for (a, b) in first.items.iter().zip(second.items.iter()) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.url, b.url);
+            assert_eq!(a.duration, b.duration);
+            assert_eq!(a.attributes, b.attributes);
+            assert_eq!(a.request_options.user_agent, b.request_options.user_agent);
+            assert_eq!(a.request_options.headers, b.request_options.headers);
+        }
+    }
+
+    #[test]
+    fn mixed_quotes_are_percent_encoded() {
+        let line = format!("#EXTINF:-1 tvg-name={},A", super::quote("Say \"Hi\" it's"));
+        let info = parse_extinf(&line);
+        assert_eq!(info.attributes["tvg-name"], "Say %22Hi%22 it's");
+        assert_eq!(info.title.as_deref(), Some("A"));
+    }
+
+    #[test]
+    fn cached_channels_round_trip() {
+        let content = "#EXTM3U
+#EXTINF:5400 tvg-id=\"film.us\" tvg-name='Say \"Hi\"' tvg-logo=\"http://example.com/film.png\" tvg-chno=\"7\" group-title=\"Movies, New\" plot=\"A film\" year=\"1999\",Film
+http://example.com/film.mkv
+#EXTINF:-1 tvg-shift=\"1.5\",Live
+http://example.com/live
+";
+        let first: Vec<_> = parse_m3u_content(content).items.into_iter().map(cached_channel).collect();
+        let second: Vec<_> = parse_m3u_content(&write_channels_m3u(&first))
+            .items
+            .into_iter()
+            .map(cached_channel)
+            .collect();
+
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.url, b.url);
+            assert_eq!(a.logo, b.logo);
+            assert_eq!(a.group_title, b.group_title);
+            assert_eq!(a.tvg_id, b.tvg_id);
+            assert_eq!(a.tvg_shift, b.tvg_shift);
+            assert_eq!(a.channel_number, b.channel_number);
+            assert_eq!(a.attributes, b.attributes);
+        }
+        assert_eq!(second[0].attributes["tvg-name"], "Say \"Hi\"");
+        assert_eq!(second[0].attributes["duration"], "5400");
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use crate::db::{self, SourceValidator};
+use crate::epg;
+use crate::playlist::{self, PlaylistBody, SourceFetch};
+use crate::types::{
+    CacheProgress, CachedCategory, CachedChannel, EpgRefreshSummary, PlaylistRefreshed, ScheduledPlaylist, XtreamCredentials,
+};
+use crate::xtream::{self, XtreamClient};
+
+/// How often the scheduler looks for playlists that are due
+const TICK: Duration = Duration::from_secs(60);
+
+pub const DEFAULT_INTERVAL_MINS: u32 = 12 * 60;
+const MIN_INTERVAL_MINS: u32 = 15;
+
+/// Fixed namespace for content fingerprints (SHA-1 based UUID v5)
+const HASH_NAMESPACE: uuid::Uuid = uuid::Uuid::from_u128(0x3b9e_71d2_5c04_4a8f_b6e3_0d2a_9f51_c7e8);
+
+/// One lock per playlist, so scheduled and manual refreshes of a playlist
+/// never run at the same time
+static REFRESH_LOCKS: LazyLock<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// Xtream logins by playlist id. They are never stored, so the app
+/// registers them again through `schedule` after every start.
+static CREDENTIALS: LazyLock<std::sync::Mutex<HashMap<String, XtreamCredentials>>> =
+    LazyLock::new(Default::default);
+
+/// Hold while writing a playlist's cache or guide outside the scheduler,
+/// so the write never overlaps a scheduled refresh of the same playlist.
+pub async fn lock_refresh(playlist_id: &str) -> OwnedMutexGuard<()> {
+    let lock = REFRESH_LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(playlist_id.to_string())
+        .or_default()
+        .clone();
+    lock.lock_owned().await
+}
+
+fn remember_credentials(playlist_id: &str, credentials: &XtreamCredentials) {
+    if let Ok(mut known) = CREDENTIALS.lock() {
+        known.insert(playlist_id.to_string(), credentials.clone());
+    }
+}
+
+fn known_credentials(playlist_id: &str) -> Option<XtreamCredentials> {
+    CREDENTIALS.lock().ok()?.get(playlist_id).cloned()
+}
+
+fn content_hash(bytes: &[u8]) -> String {
+    uuid::Uuid::new_v5(&HASH_NAMESPACE, bytes).simple().to_string()
+}
+
+/// New content of a playlist that changed since the last refresh
+enum Content {
+    /// M3U body, parsed while it streams into the cache
+    M3u(PlaylistBody),
+    Xtream(Vec<CachedCategory>, Vec<CachedChannel>),
+}
+
+struct PlaylistUpdate {
+    content: Content,
+    validator_key: String,
+    validator: SourceValidator,
+}
+
+async fn fetch_m3u(playlist: &ScheduledPlaylist) -> Result<Option<PlaylistUpdate>, String> {
+    let url = playlist
+        .url
+        .clone()
+        .ok_or("M3U playlist has no URL")?;
+    let key = format!("m3u:{}", playlist.playlist_id);
+    let stored = db::get_source_validator(key.clone()).await?.unwrap_or_default();
+    
+    let fetched = playlist::fetch_playlist_if_changed(
+        &url,
+        stored.etag.as_deref(),
+        stored.last_modified.as_deref(),
+    )
+    .await?;
+    
+    let SourceFetch::Body { body, etag, last_modified } = fetched else {
+        return Ok(None);
+    };
+    
+    // The body is streamed into the cache, which skips unchanged channels
+    // itself, so there is no whole-body hash to compare
+    Ok(Some(PlaylistUpdate {
+        content: Content::M3u(body),
+        validator_key: key,
+        validator: SourceValidator {
+            etag,
+            last_modified,
+            content_hash: None,
+            checked_at: chrono::Utc::now().timestamp(),
+        },
+    }))
+}
+
+async fn fetch_xtream(playlist: &ScheduledPlaylist) -> Result<Option<PlaylistUpdate>, String> {
+    let credentials = playlist
+        .credentials
+        .as_ref()
+        .ok_or("Xtream credentials are not loaded yet; open the playlist to resume refreshes")?;
+    let key = format!("xtream:{}", playlist.playlist_id);
+    let stored = db::get_source_validator(key.clone()).await?.unwrap_or_default();
+    
+    let (categories, channels) = xtream::fetch_playlist(credentials).await?;
+    
+    // Xtream panels send no validators, so compare what came back instead
+    let serialized = serde_json::to_vec(&(&categories, &channels))
+        .map_err(|e| format!("Failed to serialize playlist: {}", e))?;
+    let hash = content_hash(&serialized);
+    if stored.content_hash.as_deref() == Some(hash.as_str()) {
+        return Ok(None);
+    }
+    
+    Ok(Some(PlaylistUpdate {
+        content: Content::Xtream(categories, channels),
+        validator_key: key,
+        validator: SourceValidator {
+            content_hash: Some(hash),
+            checked_at: chrono::Utc::now().timestamp(),
+            ..Default::default()
+        },
+    }))
+}
+
+/// Current validators of every guide source, and whether any source
+/// changed or can't tell (no `ETag`/`Last-Modified`). Validators are keyed
+/// by playlist and source index: guide URLs can carry Xtream credentials.
+async fn check_guides(playlist_id: &str, sources: &[String]) -> (bool, Vec<(String, SourceValidator)>) {
+    let now = chrono::Utc::now().timestamp();
+    let mut changed = false;
+    let mut validators = Vec::new();
+    
+    for (index, source) in sources.iter().enumerate() {
+        let Ok((etag, last_modified)) = epg::guide_validators(source).await else {
+            changed = true;
+            continue;
+        };
+        let key = format!("epg:{}:{}", playlist_id, index);
+        let stored = db::get_source_validator(key.clone()).await.ok().flatten();
+        changed |= (etag.is_none() && last_modified.is_none())
+            || !stored.is_some_and(|s| s.etag == etag && s.last_modified == last_modified);
+        validators.push((
+            key,
+            SourceValidator {
+                etag,
+                last_modified,
+                content_hash: None,
+                checked_at: now,
+            },
+        ));
+    }
+    
+    (changed, validators)
+}
+
+/// Refresh the playlist's guide unless every source reports it unchanged.
+async fn refresh_guide(playlist: &ScheduledPlaylist) -> Result<Option<EpgRefreshSummary>, String> {
+    let id = playlist.playlist_id.clone();
+    let xtream_credentials = playlist.credentials.clone().filter(|_| playlist.kind == "xtream");
+    
+    let sources = if !playlist.epg_urls.is_empty() {
+        playlist.epg_urls.clone()
+    } else if let Some(credentials) = &xtream_credentials {
+        vec![XtreamClient::new(credentials)?.xmltv_url()]
+    } else {
+        playlist.tvg_urls.clone()
+    };
+    if sources.is_empty() {
+        return Ok(None);
+    }
+    
+    let (changed, validators) = check_guides(&id, &sources).await;
+    if !changed {
+        return Ok(None);
+    }
+    
+    let summary = match xtream_credentials.filter(|_| playlist.epg_urls.is_empty()) {
+        Some(credentials) => xtream::refresh_epg(id, credentials).await?,
+        None => epg::refresh_epg(id, sources.clone(), None).await?,
+    };
+    
+    for (key, validator) in validators {
+        db::save_source_validator(key, validator).await?;
+    }
+    
+    Ok(Some(summary))
+}
+
+async fn refresh_sources(
+    app: &tauri::AppHandle,
+    playlist: &mut ScheduledPlaylist,
+) -> Result<PlaylistRefreshed, String> {
+    let id = playlist.playlist_id.clone();
+    
+    let update = match playlist.kind.as_str() {
+        "m3u" => fetch_m3u(playlist).await?,
+        "xtream" => fetch_xtream(playlist).await?,
+        other => return Err(format!("Unknown playlist kind: {}", other)),
+    };
+    
+    let mut refreshed = PlaylistRefreshed {
+        playlist_id: id.clone(),
+        changed: false,
+        added: 0,
+        updated: 0,
+        removed: 0,
+        total: 0,
+        epg: None,
+    };
+    
+    if let Some(update) = update {
+        let on_progress = |progress: CacheProgress| {
+            let _ = app.emit("cache-progress", &progress);
+        };
+        let summary = match update.content {
+            Content::M3u(body) => {
+                let (summary, header) = playlist::cache_m3u(id.clone(), body, &on_progress).await?;
+                playlist.tvg_urls = header.epg_urls;
+                summary
+            }
+            Content::Xtream(categories, channels) => {
+                playlist.tvg_urls = Vec::new();
+                db::cache_playlist_data(id.clone(), categories, channels, on_progress).await?
+            }
+        };
+        let channels = summary.channels;
+        refreshed.changed = channels.added + channels.updated + channels.removed > 0;
+        refreshed.added = channels.added;
+        refreshed.updated = channels.updated;
+        refreshed.removed = channels.removed;
+        refreshed.total = channels.added + channels.updated + channels.unchanged;
+        
+        db::save_source_validator(update.validator_key, update.validator).await?;
+    }
+    
+    // A broken guide shouldn't hide a successful playlist refresh
+    match refresh_guide(playlist).await {
+        Ok(summary) => refreshed.epg = summary,
+        Err(e) => eprintln!("Failed to refresh EPG for {}: {}", id, e),
+    }
+    
+    // New channels need linking even when the guide itself didn't change
+    if refreshed.changed && refreshed.epg.is_none() {
+        epg::match_channels(id).await?;
+    }
+    
+    Ok(refreshed)
+}
+
+/// Refresh one scheduled playlist now, recording the outcome and emitting
+/// `playlist-refreshed` when anything changed.
+pub async fn refresh_playlist(
+    app: &tauri::AppHandle,
+    mut playlist: ScheduledPlaylist,
+) -> Result<PlaylistRefreshed, String> {
+    let _guard = lock_refresh(&playlist.playlist_id).await;
+    println!("Rust: Refreshing playlist {}", playlist.playlist_id);
+    
+    if playlist.kind == "xtream" && playlist.credentials.is_none() {
+        playlist.credentials = known_credentials(&playlist.playlist_id);
+    }
+    
+    let result = refresh_sources(app, &mut playlist).await;
+    let now = chrono::Utc::now().timestamp();
+    playlist.last_checked = Some(now);
+    
+    match &result {
+        Ok(refreshed) => {
+            playlist.last_error = None;
+            if refreshed.changed {
+                playlist.last_changed = Some(now);
+            }
+            if refreshed.changed || refreshed.epg.is_some() {
+                let _ = app.emit("playlist-refreshed", refreshed);
+            }
+        }
+        Err(e) => playlist.last_error = Some(e.clone()),
+    }
+    
+    db::save_scheduled_playlist(playlist).await?;
+    result
+}
+
+fn is_due(playlist: &ScheduledPlaylist, now: i64) -> bool {
+    let interval = playlist.interval_mins.max(MIN_INTERVAL_MINS) as i64 * 60;
+    playlist.enabled && playlist.last_checked.is_none_or(|checked| now - checked >= interval)
+}
+
+async fn run_due(app: &tauri::AppHandle) -> Result<(), String> {
+    let now = chrono::Utc::now().timestamp();
+    
+    for playlist in db::get_scheduled_playlists().await? {
+        if !is_due(&playlist, now) {
+            continue;
+        }
+        let id = playlist.playlist_id.clone();
+        if let Err(e) = refresh_playlist(app, playlist).await {
+            eprintln!("Scheduled refresh of {} failed: {}", id, e);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Register (or update) a playlist for background refreshes, keeping the
+/// status of an existing registration. Xtream credentials are held in memory.
+pub async fn schedule(mut playlist: ScheduledPlaylist) -> Result<ScheduledPlaylist, String> {
+    match playlist.kind.as_str() {
+        "m3u" if playlist.url.as_deref().is_none_or(|u| u.trim().is_empty()) => {
+            return Err("M3U playlists need a URL".to_string());
+        }
+        "xtream" if playlist.credentials.is_none() && known_credentials(&playlist.playlist_id).is_none() => {
+            return Err("Xtream playlists need credentials".to_string());
+        }
+        "m3u" | "xtream" => {}
+        other => return Err(format!("Unknown playlist kind: {}", other)),
+    }
+    
+    if playlist.interval_mins == 0 {
+        playlist.interval_mins = DEFAULT_INTERVAL_MINS;
+    }
+    playlist.interval_mins = playlist.interval_mins.max(MIN_INTERVAL_MINS);
+    
+    if let Some(credentials) = &playlist.credentials {
+        remember_credentials(&playlist.playlist_id, credentials);
+    }
+    
+    if let Some(existing) = db::get_scheduled_playlist(playlist.playlist_id.clone()).await? {
+        playlist.tvg_urls = existing.tvg_urls;
+        playlist.last_checked = existing.last_checked;
+        playlist.last_changed = existing.last_changed;
+        playlist.last_error = existing.last_error;
+    }
+    
+    db::save_scheduled_playlist(playlist.clone()).await?;
+    Ok(playlist)
+}
+
+/// Stop refreshing a playlist in the background and forget its credentials.
+pub async fn unschedule(playlist_id: String) -> Result<(), String> {
+    if let Ok(mut known) = CREDENTIALS.lock() {
+        known.remove(&playlist_id);
+    }
+    db::delete_scheduled_playlist(playlist_id).await
+}
+
+/// Start the background loop. Call once the database is ready.
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = run_due(&app).await {
+                eprintln!("Playlist scheduler failed: {}", e);
+            }
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
@@ -5,6 +5,8 @@ use tokio::process::Command;
 use tokio::sync::Mutex;
 use warp::Filter;
 
+use crate::types::StreamRequestOptions;
+
 pub struct TranscodeState {
     pub ffmpeg_process: Arc<Mutex<Option<tokio::process::Child>>>,
     pub server_port: Arc<Mutex<Option<u16>>>,
@@ -57,9 +59,37 @@ pub async fn stop_transcode_internal(state: &TranscodeState) {
     }
 }
 
+/// FFmpeg input options carrying the playlist's HTTP headers for remote sources
+fn input_args(source_path: &str, options: &StreamRequestOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    if !(source_path.starts_with("http://") || source_path.starts_with("https://")) {
+        return args;
+    }
+    
+    if let Some(user_agent) = &options.user_agent {
+        args.push("-user_agent".to_string());
+        args.push(user_agent.clone());
+    }
+    if let Some(referrer) = &options.referrer {
+        args.push("-referer".to_string());
+        args.push(referrer.clone());
+    }
+    if !options.headers.is_empty() {
+        let headers: String = options
+            .headers
+            .iter()
+            .map(|(key, value)| format!("{}: {}\r\n", key, value))
+            .collect();
+        args.push("-headers".to_string());
+        args.push(headers);
+    }
+    args
+}
+
 pub async fn start_transcode(
     state: &TranscodeState,
     source_path: &str,
+    request_options: Option<StreamRequestOptions>,
 ) -> Result<String, String> {
     println!("Starting transcode for: {}", source_path);
     
@@ -78,7 +108,9 @@ pub async fn start_transcode(
     let port = find_available_port().await?;
     
     // Start FFmpeg process
+    let request_options = request_options.unwrap_or_default();
     let ffmpeg = Command::new("ffmpeg")
+        .args(input_args(source_path, &request_options))
         .args([
             "-i", source_path,
             "-c:v", "libx264",
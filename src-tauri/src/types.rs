@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -5,15 +6,84 @@ pub struct PlaylistItem {
     pub id: String,
     pub name: String,
     pub url: String,
+    pub duration: Option<f64>,
     pub tvg_id: Option<String>,
+    pub tvg_name: Option<String>,
     pub tvg_logo: Option<String>,
+    pub tvg_chno: Option<u32>,
+    pub tvg_shift: Option<f32>,
+    pub tvg_country: Option<String>,
+    pub tvg_language: Option<String>,
+    pub tvg_rec: Option<u32>,
     pub group_title: String,
+    pub catchup: Option<String>,
+    pub catchup_days: Option<u32>,
+    pub catchup_source: Option<String>,
+    pub radio: bool,
+    /// Every attribute from the `#EXTINF` line, including the ones above
+    pub attributes: HashMap<String, String>,
+    pub request_options: StreamRequestOptions,
+}
+
+/// Per-stream HTTP options collected from `#EXTVLCOPT`, `#EXTHTTP` and `#KODIPROP`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct StreamRequestOptions {
+    pub user_agent: Option<String>,
+    pub referrer: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub vlc_options: HashMap<String, String>,
+    pub kodi_props: HashMap<String, String>,
+}
+
+/// Playlist-level metadata from the `#EXTM3U` header line
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PlaylistHeader {
+    /// EPG locations from `url-tvg` / `x-tvg-url`
+    pub epg_urls: Vec<String>,
+    pub tvg_shift: Option<f32>,
+    pub catchup: Option<String>,
+    pub catchup_days: Option<u32>,
+    pub catchup_source: Option<String>,
+    pub attributes: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ParsedPlaylist {
     pub items: Vec<PlaylistItem>,
     pub categories: Vec<String>,
+    pub header: PlaylistHeader,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ParseIssue {
+    /// 1-based line number in the source playlist
+    pub line: usize,
+    pub message: String,
+}
+
+/// Diagnostics collected while parsing a playlist
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ParseReport {
+    pub lines_read: usize,
+    pub items_parsed: usize,
+    pub skipped: Vec<ParseIssue>,
+    pub warnings: Vec<ParseIssue>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ParsedPlaylistReport {
+    pub playlist: ParsedPlaylist,
+    pub report: ParseReport,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlaylistLoadProgress {
+    pub url: String,
+    pub bytes_read: u64,
+    pub total_bytes: Option<u64>,
+    pub items_parsed: usize,
+    pub status: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -55,6 +125,384 @@ pub struct CachedChannel {
     pub category_id: Option<String>,
     pub stream_id: Option<String>,
     pub container_extension: Option<String>,
+    pub tvg_id: Option<String>,
+    /// Hours to shift this channel's guide by
+    pub tvg_shift: Option<f32>,
+    /// Provider channel number (`tvg-chno`, Xtream `num`)
+    #[serde(default)]
+    pub channel_number: Option<u32>,
+    #[serde(default)]
+    pub rating: Option<f64>,
+    /// Unix timestamp the provider added the entry, or when it was first cached
+    #[serde(default)]
+    pub added_at: Option<i64>,
+    /// Details an M3U entry carries as `#EXTINF` attributes, e.g. `plot`
+    /// and `year`, used as its VOD metadata and written back on export
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChangeCounts {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// What `cache_playlist_data` changed in the cache
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CacheSummary {
+    pub playlist_id: String,
+    pub categories: ChangeCounts,
+    pub channels: ChangeCounts,
+}
+
+/// Order for paged channel listings
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelSort {
+    /// A to Z
+    #[default]
+    Name,
+    /// Newest first
+    Added,
+    /// The order the provider lists them in
+    Provider,
+    /// Highest rated first
+    Rating,
+    /// Lowest channel number first
+    Number,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChannelPage {
+    pub channels: Vec<CachedChannel>,
+    /// Opaque; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Where a search matched, in characters from the start of `field`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MatchSpan {
+    /// "name", "group_title", or a VOD metadata field such as "plot"
+    pub field: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChannelSearchResult {
+    #[serde(flatten)]
+    pub channel: CachedChannel,
+    /// Higher is better; matched words count in whole points, BM25 breaks ties
+    pub score: f64,
+    pub highlights: Vec<MatchSpan>,
+}
+
+/// A channel identified by its playlist and stable id
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChannelSource {
+    pub playlist_id: String,
+    pub content_type: String,
+    pub channel_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GlobalSearchHit {
+    #[serde(flatten)]
+    pub result: ChannelSearchResult,
+    pub also_in: Vec<ChannelSource>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlaylistSearchGroup {
+    pub playlist_id: String,
+    /// Results the playlist returned, at most the search limit, counting
+    /// ones listed under another playlist
+    pub returned: usize,
+    pub results: Vec<GlobalSearchHit>,
+}
+
+/// Results of `search_all_playlists`, grouped by the playlist each hit is listed under
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GlobalSearchResults {
+    pub total: usize,
+    pub groups: Vec<PlaylistSearchGroup>,
+}
+
+/// Payload of the `cache-progress` event
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheProgress {
+    pub playlist_id: String,
+    pub written: usize,
+    /// Grows while a streamed playlist is still being parsed
+    pub total: usize,
+    /// "writing" while staging, "committing", then "completed" or "rolled_back"
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Series {
+    pub id: String,
+    pub playlist_id: String,
+    pub name: String,
+    pub cover: Option<String>,
+    pub plot: Option<String>,
+    pub cast: Option<String>,
+    pub director: Option<String>,
+    pub genre: Option<String>,
+    pub release_date: Option<String>,
+    pub rating: Option<f64>,
+    pub backdrop: Option<String>,
+    pub tmdb_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Season {
+    pub series_id: String,
+    pub season_number: i64,
+    pub name: String,
+    pub overview: Option<String>,
+    pub air_date: Option<String>,
+    pub cover: Option<String>,
+    pub episode_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Episode {
+    pub id: String,
+    pub series_id: String,
+    pub season_number: i64,
+    pub episode_num: Option<i64>,
+    pub title: String,
+    pub url: String,
+    pub container_extension: Option<String>,
+    pub plot: Option<String>,
+    pub cover: Option<String>,
+    pub duration_secs: Option<i64>,
+    pub rating: Option<f64>,
+    pub release_date: Option<String>,
+}
+
+/// A series with its seasons and every episode, as cached from `get_series_info`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeriesDetails {
+    pub series: Series,
+    pub seasons: Vec<Season>,
+    pub episodes: Vec<Episode>,
+}
+
+/// Detail-screen metadata for a movie, cached per channel
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VodMetadata {
+    pub playlist_id: String,
+    pub channel_id: String,
+    /// Content type of the cached channel: "movie" from Xtream, "live" from M3U
+    pub content_type: String,
+    pub plot: Option<String>,
+    pub genre: Option<String>,
+    pub director: Option<String>,
+    pub cast: Option<String>,
+    pub rating: Option<f64>,
+    pub duration_secs: Option<i64>,
+    pub release_date: Option<String>,
+    pub cover: Option<String>,
+    pub backdrop: Option<String>,
+    pub tmdb_id: Option<String>,
+    pub youtube_trailer: Option<String>,
+    /// Unix timestamp of when this was fetched from the provider
+    pub fetched_at: i64,
+}
+
+/// A `<channel>` entry from an XMLTV guide
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EpgChannel {
+    pub id: String,
+    pub display_names: Vec<String>,
+    pub icon: Option<String>,
+}
+
+/// A single guide entry; `start` and `stop` are Unix timestamps in UTC
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EpgProgramme {
+    pub channel_id: String,
+    pub start: i64,
+    pub stop: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub categories: Vec<String>,
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EpgNowNext {
+    pub now: Option<EpgProgramme>,
+    pub next: Option<EpgProgramme>,
+}
+
+/// Link between a playlist channel and an EPG channel
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EpgMapping {
+    pub playlist_id: String,
+    pub channel_id: String,
+    /// `None` on a manual override means "no guide for this channel"
+    pub epg_channel_id: Option<String>,
+    /// 1.0 for tvg-id and manual matches, lower for name-based guesses
+    pub confidence: f64,
+    /// "tvg_id", "name", "fuzzy" or "manual"
+    pub method: String,
+    pub manual: bool,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EpgMatchSummary {
+    pub playlist_id: String,
+    pub channels: usize,
+    pub by_tvg_id: usize,
+    pub by_name: usize,
+    pub fuzzy: usize,
+    pub manual: usize,
+    pub unmatched: usize,
+}
+
+/// Per-playlist guide corrections
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EpgSettings {
+    pub playlist_id: String,
+    /// Added to every channel's own `tvg-shift` at query time
+    pub shift_hours: f64,
+    /// IANA zone for guide times without an offset; the provider's zone or UTC when unset
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EpgRefreshSummary {
+    pub playlist_id: String,
+    pub sources: Vec<String>,
+    pub channels: usize,
+    pub programmes: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct XtreamCredentials {
+    pub server_url: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Subscription and server details from an Xtream login
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct XtreamAccount {
+    pub playlist_id: String,
+    pub username: Option<String>,
+    pub status: Option<String>,
+    /// Unix timestamp; `None` for accounts without expiry
+    pub exp_date: Option<i64>,
+    pub is_trial: bool,
+    pub active_connections: Option<i64>,
+    pub max_connections: Option<i64>,
+    pub created_at: Option<i64>,
+    pub allowed_output_formats: Vec<String>,
+    pub server_url: Option<String>,
+    pub server_port: Option<String>,
+    pub server_https_port: Option<String>,
+    pub server_protocol: Option<String>,
+    pub server_timezone: Option<String>,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct XtreamAccountAlert {
+    pub playlist_id: String,
+    /// "expiring", "expired" or "connection_limit"
+    pub kind: String,
+    pub message: String,
+    pub days_remaining: Option<i64>,
+}
+
+/// A playlist the background scheduler keeps up to date
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduledPlaylist {
+    pub playlist_id: String,
+    /// "m3u" or "xtream"
+    pub kind: String,
+    /// Playlist URL or path for M3U sources
+    pub url: Option<String>,
+    /// Xtream login; only kept in memory, never written to the database
+    #[serde(default)]
+    pub credentials: Option<XtreamCredentials>,
+    /// XMLTV sources; M3U playlists fall back to their `url-tvg`
+    #[serde(default)]
+    pub epg_urls: Vec<String>,
+    /// `url-tvg` of the playlist as last downloaded
+    #[serde(default)]
+    pub tvg_urls: Vec<String>,
+    pub interval_mins: u32,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub last_checked: Option<i64>,
+    pub last_changed: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Payload of the `playlist-refreshed` event
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlaylistRefreshed {
+    pub playlist_id: String,
+    pub changed: bool,
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub total: usize,
+    pub epg: Option<EpgRefreshSummary>,
+}
+
+/// A user-made list of channels that can span playlists
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChannelGroup {
+    pub id: String,
+    pub name: String,
+    pub position: i64,
+    /// Members in the order the user arranged them
+    pub channels: Vec<ChannelSource>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupChannel {
+    pub playlist_id: String,
+    #[serde(flatten)]
+    pub channel: CachedChannel,
+}
+
+/// User changes to how a provider category is shown
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryOverride {
+    pub playlist_id: String,
+    pub content_type: String,
+    pub category_id: String,
+    /// Shown instead of the provider's name
+    pub name: Option<String>,
+    pub hidden: bool,
+    pub position: Option<i64>,
+}
+
+/// A channel, movie or series the user marked, keyed by its stable id
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Favorite {
+    pub playlist_id: String,
+    pub channel_id: String,
+    pub content_type: String,
+    /// Order within the playlist's favorites of this content type
+    pub position: i64,
+    pub added_at: i64,
+    /// `None` while the channel is missing from the cached playlist
+    pub channel: Option<CachedChannel>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
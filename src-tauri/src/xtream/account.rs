@@ -0,0 +1,94 @@
+use tauri::Emitter;
+
+use crate::db;
+use crate::types::{XtreamAccount, XtreamAccountAlert, XtreamCredentials};
+use super::{XtreamClient, XtreamLogin};
+
+/// Warn this many days before expiry unless the caller asks otherwise
+const DEFAULT_WARN_DAYS: i64 = 7;
+
+fn to_account(playlist_id: String, login: XtreamLogin) -> XtreamAccount {
+    let user = login.user_info;
+    let server = login.server_info;
+    
+    XtreamAccount {
+        playlist_id,
+        username: user.username,
+        status: user.status,
+        exp_date: user.exp_date,
+        is_trial: user.is_trial,
+        active_connections: user.active_cons,
+        max_connections: user.max_connections,
+        created_at: user.created_at,
+        allowed_output_formats: user.allowed_output_formats,
+        server_url: server.url,
+        server_port: server.port,
+        server_https_port: server.https_port,
+        server_protocol: server.server_protocol,
+        server_timezone: server.timezone,
+        updated_at: chrono::Utc::now().timestamp(),
+    }
+}
+
+/// Alerts for an account that is expired, about to expire or at its
+/// connection limit.
+pub fn account_alerts(account: &XtreamAccount, warn_days: i64) -> Vec<XtreamAccountAlert> {
+    let mut alerts = Vec::new();
+    
+    if let Some(exp_date) = account.exp_date {
+        let remaining = exp_date - chrono::Utc::now().timestamp();
+        let days_remaining = remaining.div_euclid(86_400);
+        
+        if remaining <= 0 {
+            alerts.push(XtreamAccountAlert {
+                playlist_id: account.playlist_id.clone(),
+                kind: "expired".to_string(),
+                message: "Subscription has expired".to_string(),
+                days_remaining: Some(days_remaining),
+            });
+        } else if days_remaining < warn_days {
+            alerts.push(XtreamAccountAlert {
+                playlist_id: account.playlist_id.clone(),
+                kind: "expiring".to_string(),
+                message: format!("Subscription expires in {} day(s)", days_remaining),
+                days_remaining: Some(days_remaining),
+            });
+        }
+    }
+    
+    if let (Some(active), Some(max)) = (account.active_connections, account.max_connections) {
+        if max > 0 && active >= max {
+            alerts.push(XtreamAccountAlert {
+                playlist_id: account.playlist_id.clone(),
+                kind: "connection_limit".to_string(),
+                message: format!("All {} connection(s) are in use", max),
+                days_remaining: None,
+            });
+        }
+    }
+    
+    alerts
+}
+
+/// Log in, persist the account details and emit `xtream-account-alert` for
+/// anything that needs the user's attention.
+pub async fn refresh_account(
+    app: &tauri::AppHandle,
+    playlist_id: String,
+    credentials: XtreamCredentials,
+    warn_days: Option<u32>,
+) -> Result<XtreamAccount, String> {
+    let client = XtreamClient::new(&credentials)?;
+    let login = client.authenticate().await?;
+    let account = to_account(playlist_id, login);
+    
+    db::save_xtream_account(account.clone()).await?;
+    
+    let warn_days = warn_days.map(i64::from).unwrap_or(DEFAULT_WARN_DAYS);
+    for alert in account_alerts(&account, warn_days) {
+        println!("Xtream account alert for {}: {}", alert.playlist_id, alert.message);
+        let _ = app.emit("xtream-account-alert", &alert);
+    }
+    
+    Ok(account)
+}
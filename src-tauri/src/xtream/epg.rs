@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+use base64::Engine;
+use chrono_tz::Tz;
+use futures_util::StreamExt;
+
+use crate::epg;
+use crate::types::{EpgChannel, EpgProgramme, EpgRefreshSummary, XtreamCredentials};
+use super::{XtreamClient, XtreamEpgListing, XtreamStream};
+
+/// Per-stream EPG requests in flight at once during the fallback
+const CONCURRENT_REQUESTS: usize = 8;
+
+/// Titles and descriptions are base64 on most panels but plain text on some
+fn decode_text(value: &str) -> String {
+    base64::engine::general_purpose::STANDARD
+        .decode(value.trim())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Listing times, preferring the Unix timestamps over the formatted strings,
+/// which are wall-clock times in the server's zone
+fn listing_time(timestamp: Option<i64>, formatted: Option<&str>, tz: Tz) -> Option<i64> {
+    timestamp.or_else(|| {
+        let naive = chrono::NaiveDateTime::parse_from_str(formatted?.trim(), "%Y-%m-%d %H:%M:%S").ok()?;
+        Some(epg::local_to_utc(&naive, tz))
+    })
+}
+
+fn to_programme(channel_id: &str, listing: XtreamEpgListing, tz: Tz) -> Option<EpgProgramme> {
+    let start = listing_time(listing.start_timestamp, listing.start.as_deref(), tz)?;
+    let stop = listing_time(listing.stop_timestamp, listing.end.as_deref(), tz)?;
+    
+    Some(EpgProgramme {
+        channel_id: channel_id.to_string(),
+        start,
+        stop,
+        title: decode_text(&listing.title),
+        description: listing.description.as_deref().map(decode_text).filter(|d| !d.trim().is_empty()),
+        categories: Vec::new(),
+        icon: None,
+    })
+}
+
+/// Full listing table of a stream, falling back to the short EPG on panels
+/// that don't implement `get_simple_data_table`.
+async fn stream_listings(client: &XtreamClient, stream_id: &str) -> Vec<XtreamEpgListing> {
+    match client.get_simple_data_table(stream_id).await {
+        Ok(table) if !table.epg_listings.is_empty() => table.epg_listings,
+        _ => client
+            .get_short_epg(stream_id, None)
+            .await
+            .map(|epg| epg.epg_listings)
+            .unwrap_or_default(),
+    }
+}
+
+async fn fetch_short_epg(
+    client: &XtreamClient,
+    playlist_id: String,
+    tz: Tz,
+) -> Result<EpgRefreshSummary, String> {
+    // Streams sharing an EPG channel only need to be fetched once
+    let mut seen = HashSet::new();
+    let streams: Vec<XtreamStream> = client
+        .get_live_streams(None)
+        .await?
+        .into_iter()
+        .filter(|s| s.epg_channel_id.as_deref().is_some_and(|id| seen.insert(id.to_string())))
+        .collect();
+    
+    println!("Rust: Fetching short EPG for {} Xtream streams", streams.len());
+    
+    let results: Vec<(EpgChannel, Vec<EpgProgramme>)> = futures_util::stream::iter(streams)
+        .map(|stream| async move {
+            let channel_id = stream.epg_channel_id.clone().unwrap_or_default();
+            let programmes = stream_listings(client, &stream.stream_id)
+                .await
+                .into_iter()
+                .filter_map(|listing| to_programme(&channel_id, listing, tz))
+                .collect();
+            let channel = EpgChannel {
+                id: channel_id,
+                display_names: vec![stream.name],
+                icon: stream.stream_icon,
+            };
+            (channel, programmes)
+        })
+        .buffer_unordered(CONCURRENT_REQUESTS)
+        .collect()
+        .await;
+    
+    let mut channels = Vec::new();
+    let mut programmes = Vec::new();
+    for (channel, listings) in results {
+        channels.push(channel);
+        programmes.extend(listings);
+    }
+    
+    epg::replace_guide(playlist_id, "get_short_epg".to_string(), channels, programmes).await
+}
+
+/// Refresh an Xtream playlist's guide from `xmltv.php`, or from the
+/// per-stream EPG API when the panel doesn't serve XMLTV.
+pub async fn refresh_epg(
+    playlist_id: String,
+    credentials: XtreamCredentials,
+) -> Result<EpgRefreshSummary, String> {
+    let client = XtreamClient::new(&credentials)?;
+    let login = client.authenticate().await?;
+    let tz = epg::guide_timezone(&playlist_id, login.server_info.timezone.as_deref()).await?;
+    
+    match epg::refresh_xmltv(playlist_id.clone(), vec![client.xmltv_url()], tz).await {
+        Ok(summary) if summary.programmes > 0 => return Ok(summary),
+        Ok(_) => println!("Rust: xmltv.php returned no programmes, using short EPG"),
+        Err(e) => println!("Rust: xmltv.php unavailable ({}), using short EPG", e),
+    }
+    
+    fetch_short_epg(&client, playlist_id, tz).await
+}
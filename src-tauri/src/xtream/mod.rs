@@ -0,0 +1,383 @@
+mod account;
+mod epg;
+mod models;
+mod series;
+mod vod;
+
+use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use tauri::Emitter;
+
+use crate::db;
+use crate::types::{CacheSummary, CachedCategory, CachedChannel, XtreamCredentials};
+
+pub use account::refresh_account;
+pub use epg::refresh_epg;
+pub use models::*;
+pub use series::fetch_and_cache_series;
+pub use vod::get_vod_metadata;
+
+/// Typed client for an Xtream Codes `player_api.php` endpoint
+pub struct XtreamClient {
+    http: reqwest::Client,
+    server_url: String,
+    username: String,
+    password: String,
+}
+
+/// Items of a list response. Anything but an array or an empty `{}` is an
+/// error, so a panel failure can't pass for an empty list and wipe the cache.
+/// Items that don't parse are skipped and reported.
+fn list_items<T: DeserializeOwned>(action: &str, value: serde_json::Value) -> Result<Vec<T>, String> {
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        // Some panels send an empty list as `{}`
+        serde_json::Value::Object(map) if map.is_empty() => return Ok(Vec::new()),
+        other => {
+            return Err(format!(
+                "Unexpected Xtream response for {}: expected a list, got {}",
+                action,
+                other.to_string().chars().take(200).collect::<String>()
+            ))
+        }
+    };
+    
+    let total = items.len();
+    let mut first_error = None;
+    let parsed: Vec<T> = items
+        .into_iter()
+        .filter_map(|item| match serde_json::from_value(item) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                first_error.get_or_insert(e);
+                None
+            }
+        })
+        .collect();
+    if let Some(e) = first_error {
+        eprintln!(
+            "Skipped {} of {} items in Xtream {} response: {}",
+            total - parsed.len(),
+            total,
+            action,
+            e
+        );
+    }
+    
+    Ok(parsed)
+}
+
+impl XtreamClient {
+    pub fn new(credentials: &XtreamCredentials) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(60))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        
+        let mut server_url = credentials.server_url.trim().trim_end_matches('/').to_string();
+        if !server_url.starts_with("http://") && !server_url.starts_with("https://") {
+            server_url = format!("http://{}", server_url);
+        }
+        
+        Ok(Self {
+            http,
+            server_url,
+            username: credentials.username.clone(),
+            password: credentials.password.clone(),
+        })
+    }
+    
+    pub fn server_url(&self) -> &str {
+        &self.server_url
+    }
+    
+    async fn call<T: DeserializeOwned>(
+        &self,
+        action: Option<&str>,
+        params: &[(&str, &str)],
+    ) -> Result<T, String> {
+        let mut query: Vec<(&str, &str)> = vec![
+            ("username", self.username.as_str()),
+            ("password", self.password.as_str()),
+        ];
+        if let Some(action) = action {
+            query.push(("action", action));
+        }
+        query.extend_from_slice(params);
+        
+        let response = self
+            .http
+            .get(format!("{}/player_api.php", self.server_url))
+            .query(&query)
+            .header("Accept", "application/json")
+            .header("User-Agent", "WatchTV/1.0")
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Xtream server: {}", e))?;
+        
+        if !response.status().is_success() {
+            return Err(format!("HTTP error: {}", response.status()));
+        }
+        
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to read Xtream response: {}", e))?;
+        
+        serde_json::from_slice(&body)
+            .map_err(|e| format!("Failed to parse Xtream response for {}: {}", action.unwrap_or("login"), e))
+    }
+    
+    async fn call_list<T: DeserializeOwned>(
+        &self,
+        action: &str,
+        category_id: Option<&str>,
+    ) -> Result<Vec<T>, String> {
+        let params: Vec<(&str, &str)> = category_id
+            .map(|id| vec![("category_id", id)])
+            .unwrap_or_default();
+        let value: serde_json::Value = self.call(Some(action), &params).await?;
+        list_items(action, value)
+    }
+    
+    pub async fn authenticate(&self) -> Result<XtreamLogin, String> {
+        let login: XtreamLogin = self.call(None, &[]).await?;
+        if login.user_info.auth != Some(1) {
+            return Err("Xtream authentication failed".to_string());
+        }
+        Ok(login)
+    }
+    
+    pub async fn get_live_categories(&self) -> Result<Vec<XtreamCategory>, String> {
+        self.call_list("get_live_categories", None).await
+    }
+    
+    pub async fn get_live_streams(&self, category_id: Option<&str>) -> Result<Vec<XtreamStream>, String> {
+        self.call_list("get_live_streams", category_id).await
+    }
+    
+    pub async fn get_vod_categories(&self) -> Result<Vec<XtreamCategory>, String> {
+        self.call_list("get_vod_categories", None).await
+    }
+    
+    pub async fn get_vod_streams(&self, category_id: Option<&str>) -> Result<Vec<XtreamStream>, String> {
+        self.call_list("get_vod_streams", category_id).await
+    }
+    
+    pub async fn get_series_categories(&self) -> Result<Vec<XtreamCategory>, String> {
+        self.call_list("get_series_categories", None).await
+    }
+    
+    pub async fn get_series(&self, category_id: Option<&str>) -> Result<Vec<XtreamSeries>, String> {
+        self.call_list("get_series", category_id).await
+    }
+    
+    pub async fn get_series_info(&self, series_id: &str) -> Result<XtreamSeriesInfo, String> {
+        self.call(Some("get_series_info"), &[("series_id", series_id)]).await
+    }
+    
+    pub async fn get_vod_info(&self, vod_id: &str) -> Result<XtreamVodInfo, String> {
+        self.call(Some("get_vod_info"), &[("vod_id", vod_id)]).await
+    }
+    
+    pub async fn get_short_epg(&self, stream_id: &str, limit: Option<u32>) -> Result<XtreamShortEpg, String> {
+        let limit = limit.map(|l| l.to_string());
+        let mut params = vec![("stream_id", stream_id)];
+        if let Some(limit) = limit.as_deref() {
+            params.push(("limit", limit));
+        }
+        self.call(Some("get_short_epg"), &params).await
+    }
+    
+    /// Every listing of a stream, in the same shape as `get_short_epg`
+    pub async fn get_simple_data_table(&self, stream_id: &str) -> Result<XtreamShortEpg, String> {
+        self.call(Some("get_simple_data_table"), &[("stream_id", stream_id)]).await
+    }
+    
+    /// XMLTV guide for the whole account
+    pub fn xmltv_url(&self) -> String {
+        let base = format!("{}/xmltv.php", self.server_url);
+        reqwest::Url::parse_with_params(&base, &[("username", &self.username), ("password", &self.password)])
+            .map(|url| url.to_string())
+            .unwrap_or(base)
+    }
+    
+    pub fn live_url(&self, stream_id: &str) -> String {
+        format!("{}/live/{}/{}/{}.ts", self.server_url, self.username, self.password, stream_id)
+    }
+    
+    pub fn movie_url(&self, stream_id: &str, extension: Option<&str>) -> String {
+        format!(
+            "{}/movie/{}/{}/{}.{}",
+            self.server_url,
+            self.username,
+            self.password,
+            stream_id,
+            extension.unwrap_or("mp4")
+        )
+    }
+    
+    pub fn series_url(&self, id: &str, extension: Option<&str>) -> String {
+        format!(
+            "{}/series/{}/{}/{}.{}",
+            self.server_url,
+            self.username,
+            self.password,
+            id,
+            extension.unwrap_or("mp4")
+        )
+    }
+}
+
+fn to_categories(categories: Vec<XtreamCategory>, content_type: &str) -> Vec<CachedCategory> {
+    categories
+        .into_iter()
+        .map(|c| CachedCategory {
+            id: c.category_id,
+            name: c.category_name,
+            content_type: content_type.to_string(),
+        })
+        .collect()
+}
+
+fn category_name(categories: &[CachedCategory], category_id: &Option<String>) -> Option<String> {
+    let id = category_id.as_ref()?;
+    categories
+        .iter()
+        .find(|c| &c.id == id)
+        .map(|c| c.name.clone())
+        .or_else(|| Some(id.clone()))
+}
+
+/// Fetch every category and stream of an Xtream account as cache rows.
+pub async fn fetch_playlist(
+    credentials: &XtreamCredentials,
+) -> Result<(Vec<CachedCategory>, Vec<CachedChannel>), String> {
+    let client = XtreamClient::new(credentials)?;
+    client.authenticate().await?;
+    
+    let mut categories: Vec<CachedCategory> = Vec::new();
+    let mut channels: Vec<CachedChannel> = Vec::new();
+    
+    // Live
+    let live_categories = to_categories(client.get_live_categories().await?, "live");
+    for stream in client.get_live_streams(None).await? {
+        channels.push(CachedChannel {
+            id: stream.stream_id.clone(),
+            name: stream.name,
+            url: client.live_url(&stream.stream_id),
+            logo: stream.stream_icon,
+            group_title: category_name(&live_categories, &stream.category_id),
+            content_type: "live".to_string(),
+            category_id: stream.category_id,
+            stream_id: Some(stream.stream_id),
+            container_extension: None,
+            tvg_id: stream.epg_channel_id,
+            tvg_shift: None,
+            channel_number: stream.num.and_then(|n| u32::try_from(n).ok()),
+            rating: stream.rating,
+            added_at: stream.added.as_deref().and_then(|a| a.trim().parse().ok()),
+            attributes: HashMap::new(),
+        });
+    }
+    categories.extend(live_categories);
+    
+    // Movies
+    let vod_categories = to_categories(client.get_vod_categories().await?, "movie");
+    for stream in client.get_vod_streams(None).await? {
+        channels.push(CachedChannel {
+            id: stream.stream_id.clone(),
+            name: stream.name,
+            url: client.movie_url(&stream.stream_id, stream.container_extension.as_deref()),
+            logo: stream.stream_icon,
+            group_title: category_name(&vod_categories, &stream.category_id),
+            content_type: "movie".to_string(),
+            category_id: stream.category_id,
+            stream_id: Some(stream.stream_id),
+            container_extension: stream.container_extension,
+            tvg_id: None,
+            tvg_shift: None,
+            channel_number: stream.num.and_then(|n| u32::try_from(n).ok()),
+            rating: stream.rating,
+            added_at: stream.added.as_deref().and_then(|a| a.trim().parse().ok()),
+            attributes: HashMap::new(),
+        });
+    }
+    categories.extend(vod_categories);
+    
+    // Series
+    let series_categories = to_categories(client.get_series_categories().await?, "series");
+    for series in client.get_series(None).await? {
+        channels.push(CachedChannel {
+            id: series.series_id.clone(),
+            name: series.name,
+            url: client.series_url(&series.series_id, None),
+            logo: series.cover,
+            group_title: category_name(&series_categories, &series.category_id),
+            content_type: "series".to_string(),
+            category_id: series.category_id,
+            stream_id: Some(series.series_id),
+            container_extension: None,
+            tvg_id: None,
+            tvg_shift: None,
+            channel_number: series.num.and_then(|n| u32::try_from(n).ok()),
+            rating: series.rating,
+            added_at: series.last_modified.as_deref().and_then(|a| a.trim().parse().ok()),
+            attributes: HashMap::new(),
+        });
+    }
+    categories.extend(series_categories);
+    
+    println!(
+        "Fetched {} categories and {} channels from Xtream",
+        categories.len(),
+        channels.len()
+    );
+    
+    Ok((categories, channels))
+}
+
+/// Fetch every category and stream of an Xtream account and replace the
+/// playlist's cache with it.
+pub async fn fetch_and_cache_playlist(
+    app: &tauri::AppHandle,
+    playlist_id: String,
+    credentials: XtreamCredentials,
+) -> Result<CacheSummary, String> {
+    println!("Fetching Xtream playlist {}", playlist_id);
+    
+    let (categories, channels) = fetch_playlist(&credentials).await?;
+    db::cache_playlist_data(playlist_id, categories, channels, |progress| {
+        let _ = app.emit("cache-progress", &progress);
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::list_items;
+    use super::models::XtreamCategory;
+    use serde_json::json;
+
+    #[test]
+    fn list_responses_must_be_lists() {
+        let empty: Vec<XtreamCategory> = list_items("get_live_categories", json!({})).unwrap();
+        assert!(empty.is_empty());
+        let empty: Vec<XtreamCategory> = list_items("get_live_categories", json!([])).unwrap();
+        assert!(empty.is_empty());
+
+        assert!(list_items::<XtreamCategory>("get_live_categories", json!(null)).is_err());
+        assert!(list_items::<XtreamCategory>("get_live_categories", json!({"error": "Forbidden"})).is_err());
+        assert!(list_items::<XtreamCategory>("get_live_categories", json!("<html>")).is_err());
+    }
+
+    #[test]
+    fn unparsable_items_are_skipped() {
+        let items: Vec<XtreamCategory> = list_items(
+            "get_live_categories",
+            json!([{"category_id": "1", "category_name": "News"}, 42]),
+        )
+        .unwrap();
+        assert_eq!(items.len(), 1);
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize};
+
+// Xtream panels are inconsistent about JSON types: IDs and numbers arrive as
+// strings or numbers, lists as `[]`, `{}`, `false` or `null`. These helpers
+// accept all of them.
+
+pub fn de_opt_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(serde_json::Value::String(s)) if !s.is_empty() => Some(s),
+        Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+        Some(serde_json::Value::Bool(b)) => Some(b.to_string()),
+        _ => None,
+    })
+}
+
+pub fn de_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(de_opt_string(deserializer)?.unwrap_or_default())
+}
+
+pub fn de_opt_f64<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(de_opt_string(deserializer)?.and_then(|s| s.trim().parse().ok()))
+}
+
+pub fn de_opt_i64<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(de_opt_f64(deserializer)?.map(|v| v as i64))
+}
+
+pub fn de_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(serde_json::Value::Array(items)) => items
+            .into_iter()
+            .filter_map(|item| serde_json::from_value(item).ok())
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+pub fn de_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(matches!(
+        de_opt_string(deserializer)?.as_deref(),
+        Some("1") | Some("true")
+    ))
+}
+
+/// An object, or the type's default when the panel sends `[]`, `false` or `null`
+pub fn de_object<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned + Default,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(value @ serde_json::Value::Object(_)) => serde_json::from_value(value).unwrap_or_default(),
+        _ => T::default(),
+    })
+}
+
+/// A string or a list of strings (e.g. `backdrop_path`)
+pub fn de_string_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(serde_json::Value::String(s)) if !s.is_empty() => vec![s],
+        Some(serde_json::Value::Array(items)) => items
+            .into_iter()
+            .filter_map(|item| item.as_str().map(String::from))
+            .filter(|s| !s.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamUserInfo {
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub auth: Option<i64>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub status: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub username: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub message: Option<String>,
+    /// Unix timestamp; `null` for unlimited accounts
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub exp_date: Option<i64>,
+    #[serde(deserialize_with = "de_bool")]
+    pub is_trial: bool,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub active_cons: Option<i64>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub max_connections: Option<i64>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub created_at: Option<i64>,
+    #[serde(deserialize_with = "de_string_list")]
+    pub allowed_output_formats: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamServerInfo {
+    #[serde(deserialize_with = "de_opt_string")]
+    pub url: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub port: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub https_port: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub server_protocol: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub timezone: Option<String>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub timestamp_now: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamLogin {
+    #[serde(deserialize_with = "de_object")]
+    pub user_info: XtreamUserInfo,
+    #[serde(deserialize_with = "de_object")]
+    pub server_info: XtreamServerInfo,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamCategory {
+    #[serde(deserialize_with = "de_string")]
+    pub category_id: String,
+    #[serde(deserialize_with = "de_string")]
+    pub category_name: String,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub parent_id: Option<String>,
+}
+
+/// Entry from `get_live_streams` or `get_vod_streams`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamStream {
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub num: Option<i64>,
+    #[serde(deserialize_with = "de_string")]
+    pub name: String,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub stream_type: Option<String>,
+    #[serde(deserialize_with = "de_string")]
+    pub stream_id: String,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub stream_icon: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub epg_channel_id: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub added: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub category_id: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub container_extension: Option<String>,
+    #[serde(deserialize_with = "de_opt_f64")]
+    pub rating: Option<f64>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub tv_archive: Option<i64>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub tv_archive_duration: Option<i64>,
+}
+
+/// Entry from `get_series`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamSeries {
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub num: Option<i64>,
+    #[serde(deserialize_with = "de_string")]
+    pub name: String,
+    #[serde(deserialize_with = "de_string")]
+    pub series_id: String,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub cover: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub plot: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub cast: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub director: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub genre: Option<String>,
+    #[serde(rename = "releaseDate", deserialize_with = "de_opt_string")]
+    pub release_date: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub last_modified: Option<String>,
+    #[serde(deserialize_with = "de_opt_f64")]
+    pub rating: Option<f64>,
+    #[serde(deserialize_with = "de_string_list")]
+    pub backdrop_path: Vec<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub category_id: Option<String>,
+}
+
+/// Shared `info` block of `get_vod_info`, `get_series_info` and episodes
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamMediaInfo {
+    #[serde(deserialize_with = "de_opt_string")]
+    pub name: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub plot: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub description: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub cast: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub director: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub genre: Option<String>,
+    #[serde(deserialize_with = "de_opt_string", alias = "releaseDate")]
+    pub releasedate: Option<String>,
+    #[serde(deserialize_with = "de_opt_f64")]
+    pub rating: Option<f64>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub duration_secs: Option<i64>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub duration: Option<String>,
+    #[serde(deserialize_with = "de_opt_string", alias = "cover")]
+    pub movie_image: Option<String>,
+    #[serde(deserialize_with = "de_string_list")]
+    pub backdrop_path: Vec<String>,
+    #[serde(deserialize_with = "de_opt_string", alias = "tmdb")]
+    pub tmdb_id: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub youtube_trailer: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamMovieData {
+    #[serde(deserialize_with = "de_string")]
+    pub stream_id: String,
+    #[serde(deserialize_with = "de_string")]
+    pub name: String,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub container_extension: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub category_id: Option<String>,
+}
+
+/// Response of `get_vod_info`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamVodInfo {
+    #[serde(deserialize_with = "de_object")]
+    pub info: XtreamMediaInfo,
+    #[serde(deserialize_with = "de_object")]
+    pub movie_data: XtreamMovieData,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamSeason {
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub season_number: Option<i64>,
+    #[serde(deserialize_with = "de_string")]
+    pub name: String,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub episode_count: Option<i64>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub overview: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub air_date: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub cover: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamEpisode {
+    #[serde(deserialize_with = "de_string")]
+    pub id: String,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub episode_num: Option<i64>,
+    #[serde(deserialize_with = "de_string")]
+    pub title: String,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub container_extension: Option<String>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub season: Option<i64>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub added: Option<String>,
+    #[serde(deserialize_with = "de_object")]
+    pub info: XtreamMediaInfo,
+}
+
+/// Response of `get_series_info`; `episodes` is keyed by season number
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamSeriesInfo {
+    #[serde(deserialize_with = "de_vec")]
+    pub seasons: Vec<XtreamSeason>,
+    #[serde(deserialize_with = "de_object")]
+    pub info: XtreamMediaInfo,
+    #[serde(deserialize_with = "de_episodes")]
+    pub episodes: HashMap<String, Vec<XtreamEpisode>>,
+}
+
+fn de_episodes<'de, D>(deserializer: D) -> Result<HashMap<String, Vec<XtreamEpisode>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    let mut episodes: HashMap<String, Vec<XtreamEpisode>> = HashMap::new();
+    match value {
+        Some(serde_json::Value::Object(seasons)) => {
+            for (season, list) in seasons {
+                let list: Vec<XtreamEpisode> = serde_json::from_value(list).unwrap_or_default();
+                episodes.insert(season, list);
+            }
+        }
+        // Some panels send a flat array of per-season arrays
+        Some(serde_json::Value::Array(seasons)) => {
+            for list in seasons {
+                let list: Vec<XtreamEpisode> = serde_json::from_value(list).unwrap_or_default();
+                for episode in list {
+                    let season = episode.season.unwrap_or(1).to_string();
+                    episodes.entry(season).or_default().push(episode);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(episodes)
+}
+
+/// Entry from `get_short_epg`; title and description are base64-encoded
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamEpgListing {
+    #[serde(deserialize_with = "de_opt_string")]
+    pub id: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub epg_id: Option<String>,
+    #[serde(deserialize_with = "de_string")]
+    pub title: String,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub lang: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub start: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub end: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub description: Option<String>,
+    #[serde(deserialize_with = "de_opt_string")]
+    pub channel_id: Option<String>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub start_timestamp: Option<i64>,
+    #[serde(deserialize_with = "de_opt_i64")]
+    pub stop_timestamp: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct XtreamShortEpg {
+    #[serde(deserialize_with = "de_vec")]
+    pub epg_listings: Vec<XtreamEpgListing>,
+}
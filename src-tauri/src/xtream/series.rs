@@ -0,0 +1,103 @@
+use crate::db;
+use crate::types::{Episode, Season, Series, SeriesDetails, XtreamCredentials};
+use super::{XtreamClient, XtreamSeriesInfo};
+
+fn to_details(
+    client: &XtreamClient,
+    playlist_id: String,
+    series_id: String,
+    info: XtreamSeriesInfo,
+) -> SeriesDetails {
+    let meta = info.info;
+    let series = Series {
+        id: series_id.clone(),
+        playlist_id,
+        name: meta.name.unwrap_or_default(),
+        cover: meta.movie_image,
+        plot: meta.plot.or(meta.description),
+        cast: meta.cast,
+        director: meta.director,
+        genre: meta.genre,
+        release_date: meta.releasedate,
+        rating: meta.rating,
+        backdrop: meta.backdrop_path.into_iter().next(),
+        tmdb_id: meta.tmdb_id,
+    };
+    
+    let mut episodes: Vec<Episode> = Vec::new();
+    for (season_key, list) in info.episodes {
+        let key_number = season_key.parse::<i64>().ok();
+        for ep in list {
+            let season_number = ep.season.or(key_number).unwrap_or(1);
+            let ep_info = ep.info;
+            episodes.push(Episode {
+                url: client.series_url(&ep.id, ep.container_extension.as_deref()),
+                id: ep.id,
+                series_id: series_id.clone(),
+                season_number,
+                episode_num: ep.episode_num,
+                title: ep.title,
+                container_extension: ep.container_extension,
+                plot: ep_info.plot.or(ep_info.description),
+                cover: ep_info.movie_image,
+                duration_secs: ep_info.duration_secs,
+                rating: ep_info.rating,
+                release_date: ep_info.releasedate,
+            });
+        }
+    }
+    episodes.sort_by_key(|e| (e.season_number, e.episode_num));
+    
+    let episode_count = |season_number: i64| {
+        episodes.iter().filter(|e| e.season_number == season_number).count() as i64
+    };
+    
+    let mut seasons: Vec<Season> = info
+        .seasons
+        .into_iter()
+        .filter_map(|s| {
+            let season_number = s.season_number?;
+            Some(Season {
+                series_id: series_id.clone(),
+                season_number,
+                name: if s.name.is_empty() { format!("Season {}", season_number) } else { s.name },
+                overview: s.overview,
+                air_date: s.air_date,
+                cover: s.cover,
+                episode_count: s.episode_count.unwrap_or_else(|| episode_count(season_number)),
+            })
+        })
+        .collect();
+    
+    // Panels often omit the season list; derive it from the episodes
+    for ep in &episodes {
+        if !seasons.iter().any(|s| s.season_number == ep.season_number) {
+            seasons.push(Season {
+                series_id: series_id.clone(),
+                season_number: ep.season_number,
+                name: format!("Season {}", ep.season_number),
+                overview: None,
+                air_date: None,
+                cover: None,
+                episode_count: episode_count(ep.season_number),
+            });
+        }
+    }
+    seasons.sort_by_key(|s| s.season_number);
+    
+    SeriesDetails { series, seasons, episodes }
+}
+
+/// Fetch `get_series_info` for one series and cache its seasons and episodes.
+pub async fn fetch_and_cache_series(
+    playlist_id: String,
+    credentials: XtreamCredentials,
+    series_id: String,
+) -> Result<SeriesDetails, String> {
+    let client = XtreamClient::new(&credentials)?;
+    let info = client.get_series_info(&series_id).await?;
+    let details = to_details(&client, playlist_id, series_id, info);
+    
+    db::cache_series_details(details.clone()).await?;
+    Ok(details)
+}
@@ -0,0 +1,98 @@
+use crate::db;
+use crate::types::{CachedChannel, VodMetadata, XtreamCredentials};
+use super::{XtreamClient, XtreamVodInfo};
+
+/// Cached metadata older than this is refreshed from the provider
+const DEFAULT_MAX_AGE_SECS: i64 = 7 * 24 * 60 * 60;
+
+fn to_metadata(playlist_id: String, channel_id: String, info: XtreamVodInfo) -> VodMetadata {
+    let meta = info.info;
+    
+    VodMetadata {
+        playlist_id,
+        channel_id,
+        content_type: "movie".to_string(),
+        plot: meta.plot.or(meta.description),
+        genre: meta.genre,
+        director: meta.director,
+        cast: meta.cast,
+        rating: meta.rating,
+        duration_secs: meta.duration_secs,
+        release_date: meta.releasedate,
+        cover: meta.movie_image,
+        backdrop: meta.backdrop_path.into_iter().next(),
+        tmdb_id: meta.tmdb_id,
+        youtube_trailer: meta.youtube_trailer,
+        fetched_at: chrono::Utc::now().timestamp(),
+    }
+}
+
+/// Metadata from the `#EXTINF` attributes of a cached M3U entry
+fn from_attributes(playlist_id: String, channel: CachedChannel) -> VodMetadata {
+    let mut attributes = channel.attributes;
+    let mut take = |keys: &[&str]| keys.iter().find_map(|key| attributes.remove(*key));
+    
+    VodMetadata {
+        playlist_id,
+        channel_id: channel.id,
+        content_type: channel.content_type,
+        plot: take(&["plot", "description"]),
+        genre: take(&["genre"]).or(channel.group_title),
+        director: take(&["director"]),
+        cast: take(&["cast", "actors"]),
+        rating: take(&["rating"]).and_then(|r| r.trim().parse().ok()),
+        duration_secs: take(&["duration", "tvg-duration"])
+            .and_then(|d| d.trim().parse::<f64>().ok())
+            .map(|d| d as i64),
+        release_date: take(&["release-date", "year"]),
+        cover: channel.logo,
+        backdrop: take(&["backdrop"]),
+        tmdb_id: None,
+        youtube_trailer: None,
+        fetched_at: chrono::Utc::now().timestamp(),
+    }
+}
+
+/// Return cached VOD metadata, fetching `get_vod_info` when it is missing or
+/// older than `max_age_secs`. Without credentials (M3U playlists) it falls
+/// back to the attributes of the cached entry of `content_type`.
+pub async fn get_vod_metadata(
+    playlist_id: String,
+    content_type: String,
+    channel_id: String,
+    credentials: Option<XtreamCredentials>,
+    max_age_secs: Option<i64>,
+) -> Result<Option<VodMetadata>, String> {
+    let cached = db::get_vod_metadata(playlist_id.clone(), content_type.clone(), channel_id.clone()).await?;
+    
+    let max_age = max_age_secs.unwrap_or(DEFAULT_MAX_AGE_SECS);
+    let now = chrono::Utc::now().timestamp();
+    if let Some(metadata) = &cached {
+        if now - metadata.fetched_at < max_age {
+            return Ok(cached);
+        }
+    }
+    
+    let Some(credentials) = credentials else {
+        if cached.is_some() {
+            return Ok(cached);
+        }
+        let channel = db::get_cached_channel(playlist_id.clone(), content_type, channel_id).await?;
+        return Ok(channel.map(|channel| from_attributes(playlist_id, channel)));
+    };
+    
+    let client = XtreamClient::new(&credentials)?;
+    let info = match client.get_vod_info(&channel_id).await {
+        Ok(info) => info,
+        // Keep showing stale data when the provider is unreachable
+        Err(e) if cached.is_some() => {
+            eprintln!("Failed to refresh VOD metadata for {}: {}", channel_id, e);
+            return Ok(cached);
+        }
+        Err(e) => return Err(e),
+    };
+    
+    let metadata = to_metadata(playlist_id, channel_id, info);
+    db::save_vod_metadata(metadata.clone()).await?;
+    Ok(Some(metadata))
+}